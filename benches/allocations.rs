@@ -0,0 +1,131 @@
+// Counts the allocations made by crawls of a generated site, which is served by a custom fetcher so that no network (or
+// socket) allocations muddy the numbers. Run with `cargo bench`.
+
+use web_crawler_rs::{Crawler, FetchWebPageError, FetchedContent, Fetcher, LinkExtraction};
+
+use reqwest::Url;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::HashMap;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+// How many pages the site has, and how many other pages each links to.
+const PAGES: usize = 2000;
+const LINKS_PER_PAGE: usize = 20;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    // Growing a collection counts as an allocation too, since that's what pre-sizing it saves.
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(new_size, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+// The pages of the site, generated up front so that generating them isn't counted.
+struct SiteFetcher {
+    pages: HashMap<String, Vec<u8>>,
+}
+
+impl SiteFetcher {
+    fn new() -> SiteFetcher {
+        let pages = (0..PAGES).map(|page| {
+            let links: String = (1..=LINKS_PER_PAGE)
+                .map(|i| format!(r#"<a href="/articles/{}">Article {}</a>"#, (page + i) % PAGES, (page + i) % PAGES))
+                .collect();
+            let body = format!("<title>Article {}</title><h1>Article {}</h1>{}", page, page, links);
+            (format!("/articles/{}", page), body.into_bytes())
+        }).collect();
+        SiteFetcher {
+            pages,
+        }
+    }
+}
+
+impl Fetcher for SiteFetcher {
+    fn fetch(&self, url: &Url) -> Result<FetchedContent, FetchWebPageError> {
+        let body = self.pages.get(url.path()).ok_or_else(|| FetchWebPageError::Io(io::Error::other("no such page")))?;
+        Ok(FetchedContent {
+            content_type: "text/html".to_owned(),
+            body: body.clone(),
+        })
+    }
+}
+
+// Runs the given function, printing how many allocations (and bytes) it took for each page of the site.
+fn measure<T>(name: &str, f: impl FnOnce() -> T) -> T {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let allocated_bytes = ALLOCATED_BYTES.load(Ordering::Relaxed);
+    let start = Instant::now();
+    let output = f();
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+    let allocated_bytes = ALLOCATED_BYTES.load(Ordering::Relaxed) - allocated_bytes;
+    println!(
+        "{:<24} {:>9} allocations ({:>7.1} per page) {:>11} bytes ({:>8.1} per page) in {:?}",
+        name,
+        allocations,
+        allocations as f64 / PAGES as f64,
+        allocated_bytes,
+        allocated_bytes as f64 / PAGES as f64,
+        elapsed,
+    );
+    output
+}
+
+fn seed() -> Url {
+    Url::parse("bench://site/articles/0").unwrap()
+}
+
+// Crawls the whole site with the given crawler.
+fn bench(name: &str, crawler: &Crawler) {
+    let pages = measure(name, || crawler.crawl(seed()).unwrap().count());
+    assert_eq!(pages, PAGES);
+}
+
+fn main() {
+    let crawler = Crawler::builder().fetcher("bench", SiteFetcher::new()).build().unwrap();
+    bench("crawl", &crawler);
+
+    // Pre-sizing the frontier and visited set saves growing them as the crawl goes.
+    let crawler = Crawler::builder()
+        .fetcher("bench", SiteFetcher::new())
+        .with_frontier_capacity(PAGES)
+        .with_visited_capacity(PAGES)
+        .build()
+        .unwrap();
+    bench("crawl (pre-sized)", &crawler);
+
+    // The crawl itself never clones a page's info, so this is only paid by callers that clone results.
+    let crawler = Crawler::builder().fetcher("bench", SiteFetcher::new()).build().unwrap();
+    let results: Vec<_> = crawler.crawl(seed()).unwrap().collect();
+    assert_eq!(measure("clone results", || results.clone()).len(), PAGES);
+
+    // Without building documents, the crawler's own bookkeeping makes up much more of what's allocated.
+    let crawler = Crawler::builder()
+        .fetcher("bench", SiteFetcher::new())
+        .link_extraction(LinkExtraction::Streaming)
+        .build()
+        .unwrap();
+    bench("crawl (streaming)", &crawler);
+}
@@ -0,0 +1,135 @@
+use reqwest::header::{self, HeaderMap, HeaderName, HeaderValue};
+use reqwest::{RequestBuilder, StatusCode, Url};
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::{FetchedContent, FetchedResource};
+
+// Pages fetched before (e.g., by an earlier crawl), for requesting them again conditionally (with `If-None-Match` or
+// `If-Modified-Since`) with `CrawlerBuilder::response_cache`. A cache may be shared by several crawlers.
+// NOTE: responses are kept per URL and per value of each of the request headers that their `Vary` header names, so
+// that a variant is only ever revalidated by a request that would get that same variant.
+#[derive(Clone, Debug, Default)]
+pub struct ResponseCache(Arc<Mutex<HashMap<Url, CachedVariants>>>);
+
+// The cached variants of a page.
+#[derive(Debug, Default)]
+struct CachedVariants {
+    // The request headers named by `Vary` in the last response.
+    vary: Vec<HeaderName>,
+    // Each variant by the values of those headers.
+    variants: HashMap<Vec<Option<HeaderValue>>, CachedResponse>,
+}
+
+impl CachedVariants {
+    fn key(&self, request_headers: &HeaderMap) -> Vec<Option<HeaderValue>> {
+        self.vary.iter().map(|name| request_headers.get(name).cloned()).collect()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct CachedResponse {
+    pub(crate) status: StatusCode,
+    pub(crate) headers: HeaderMap,
+    pub(crate) content: FetchedContent,
+}
+
+impl CachedResponse {
+    // Makes the given request conditional on the cached response being out of date.
+    pub(crate) fn validate(&self, request: RequestBuilder) -> RequestBuilder {
+        let request = match self.headers.get(header::ETAG) {
+            Some(etag) => request.header(header::IF_NONE_MATCH, etag.clone()),
+            None => request,
+        };
+        match self.headers.get(header::LAST_MODIFIED) {
+            Some(last_modified) => request.header(header::IF_MODIFIED_SINCE, last_modified.clone()),
+            None => request,
+        }
+    }
+
+    // Updates the headers of the cached response with those of a `304 Not Modified` response to revalidating it (e.g.,
+    // a new `ETag`), which replace any of the same names.
+    // NOTE: the content headers stay as they were, since they describe the cached body rather than the empty one.
+    pub(crate) fn refresh(&mut self, not_modified_headers: &HeaderMap) {
+        for name in not_modified_headers.keys() {
+            if name == header::CONTENT_LENGTH || name == header::CONTENT_TYPE {
+                continue;
+            }
+            self.headers.remove(name);
+            for value in not_modified_headers.get_all(name) {
+                self.headers.append(name.clone(), value.clone());
+            }
+        }
+    }
+}
+
+impl ResponseCache {
+    pub fn new() -> ResponseCache {
+        ResponseCache::default()
+    }
+
+    // The number of responses cached, counting each variant of a page.
+    pub fn len(&self) -> usize {
+        self.0.lock().unwrap().values().map(|page| page.variants.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // The cached response for the given URL that a request with the given headers would get, if any.
+    pub(crate) fn get(&self, url: &Url, request_headers: &HeaderMap) -> Option<CachedResponse> {
+        let pages = self.0.lock().unwrap();
+        let page = pages.get(url)?;
+        page.variants.get(&page.key(request_headers)).cloned()
+    }
+
+    // Caches the given response to a request with the given headers, if it can be revalidated.
+    pub(crate) fn insert(&self, url: &Url, request_headers: &HeaderMap, fetched: &FetchedResource) {
+        if !fetched.headers.contains_key(header::ETAG) && !fetched.headers.contains_key(header::LAST_MODIFIED) {
+            return;
+        }
+        // The body is kept as it'll be needed again: HTML as already decoded, and anything else as it came.
+        let content = match (&fetched.html, &fetched.body) {
+            (Some(html), _) => FetchedContent {
+                content_type: "text/html; charset=utf-8".to_owned(),
+                body: html.body.clone().into_bytes(),
+            },
+            (None, Some(body)) => FetchedContent {
+                content_type: fetched.headers.get(header::CONTENT_TYPE)
+                    .and_then(|content_type| content_type.to_str().ok())
+                    .unwrap_or(&fetched.content_type)
+                    .to_owned(),
+                body: body.clone(),
+            },
+            (None, None) => return,
+        };
+
+        let mut vary = vec![];
+        for value in fetched.headers.get_all(header::VARY) {
+            for name in value.to_str().unwrap_or("*").split(',') {
+                match name.trim() {
+                    // The response varies on more than request headers, so no request can be sure to get it again.
+                    "*" => return,
+                    name => vary.extend(HeaderName::from_bytes(name.as_bytes()).ok()),
+                }
+            }
+        }
+
+        let mut pages = self.0.lock().unwrap();
+        let page = pages.entry(url.clone()).or_default();
+        if page.vary != vary {
+            *page = CachedVariants {
+                vary,
+                variants: HashMap::new(),
+            };
+        }
+        let key = page.key(request_headers);
+        page.variants.insert(key, CachedResponse {
+            status: fetched.status,
+            headers: fetched.headers.clone(),
+            content,
+        });
+    }
+}
@@ -0,0 +1,25 @@
+use csv::{Terminator, WriterBuilder};
+
+use std::io::{self, Write};
+
+use crate::CrawlResult;
+
+// Writes the given results as CSV (per RFC 4180, with CRLF line endings), with a header row and then a row per result
+// giving its URL, title (empty for resources other than HTML pages), status, depth, number of links and size in bytes.
+pub fn write_csv<'a, W: Write>(results: impl IntoIterator<Item = &'a CrawlResult>, writer: W) -> io::Result<()> {
+    let mut writer = WriterBuilder::new().terminator(Terminator::CRLF).from_writer(writer);
+    writer.write_record(["url", "title", "status", "depth", "link_count", "byte_size"])?;
+    for result in results {
+        let title = result.page.as_ref().map_or("", |page| &*page.title);
+        let link_count = result.page.as_ref().map_or(0, |page| page.links.len());
+        writer.write_record([
+            result.url.as_str(),
+            title,
+            &result.status.as_u16().to_string(),
+            &result.depth.to_string(),
+            &link_count.to_string(),
+            &result.byte_size.to_string(),
+        ])?;
+    }
+    writer.flush()
+}
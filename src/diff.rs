@@ -0,0 +1,45 @@
+use reqwest::Url;
+
+use std::collections::HashMap;
+
+use crate::CrawlResult;
+
+// The pages that differ between two crawls of a site.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CrawlDiff {
+    // Pages only in the current crawl, in the order they were crawled.
+    pub added: Vec<Url>,
+    // Pages only in the previous crawl, in the order they were crawled.
+    pub removed: Vec<Url>,
+    // Pages in both crawls whose content differs, in the order they were crawled in the current one.
+    pub changed: Vec<Url>,
+}
+
+// Compares two crawls of a site. Pages count as changed if their bodies differ, when both crawls kept them (with
+// `keep_raw_html`), or otherwise if their titles (or content types) do.
+pub fn diff(previous: &[CrawlResult], current: &[CrawlResult]) -> CrawlDiff {
+    let previous_by_url: HashMap<_, _> = previous.iter().map(|result| (&*result.url, result)).collect();
+    let current_by_url: HashMap<_, _> = current.iter().map(|result| (&*result.url, result)).collect();
+
+    let mut diff = CrawlDiff::default();
+    for result in current {
+        match previous_by_url.get(&*result.url) {
+            Some(previous_result) if is_changed(previous_result, result) => diff.changed.push(Url::clone(&result.url)),
+            Some(_) => {}
+            None => diff.added.push(Url::clone(&result.url)),
+        }
+    }
+    diff.removed = previous.iter()
+        .filter(|result| !current_by_url.contains_key(&*result.url))
+        .map(|result| Url::clone(&result.url))
+        .collect();
+    diff
+}
+
+fn is_changed(previous: &CrawlResult, current: &CrawlResult) -> bool {
+    if let (Some(previous_html), Some(current_html)) = (&previous.raw_html, &current.raw_html) {
+        return previous_html != current_html;
+    }
+    previous.content_type != current.content_type ||
+        previous.page.as_ref().map(|page| &page.title) != current.page.as_ref().map(|page| &page.title)
+}
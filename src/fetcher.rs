@@ -0,0 +1,18 @@
+use reqwest::Url;
+
+use crate::FetchWebPageError;
+
+// Fetches the resources of some scheme other than HTTP(S) (e.g., `file`), for crawling with
+// `CrawlerBuilder::fetcher`.
+pub trait Fetcher: Send + Sync {
+    fn fetch(&self, url: &Url) -> Result<FetchedContent, FetchWebPageError>;
+}
+
+// A resource fetched by a `Fetcher`, which is then treated as a successful HTTP response with the given content type
+// would be.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FetchedContent {
+    // The MIME type, possibly with parameters (e.g., `text/html; charset=utf-8`).
+    pub content_type: String,
+    pub body: Vec<u8>,
+}
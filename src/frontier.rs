@@ -0,0 +1,115 @@
+use reqwest::Url;
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// The queue of URLs waiting to be crawled. The crawler never pushes the same URL twice.
+// NOTE: URLs are shared with the visited set and the results, so that each is only allocated once.
+pub trait Frontier {
+    fn push(&mut self, url: Arc<Url>);
+
+    fn pop(&mut self) -> Option<Arc<Url>>;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// The default frontier, which crawls breadth-first.
+#[derive(Clone, Debug, Default)]
+pub struct FifoFrontier {
+    urls: VecDeque<Arc<Url>>,
+}
+
+impl FifoFrontier {
+    pub fn new() -> FifoFrontier {
+        FifoFrontier::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> FifoFrontier {
+        FifoFrontier {
+            urls: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.urls.capacity()
+    }
+}
+
+impl Frontier for FifoFrontier {
+    fn push(&mut self, url: Arc<Url>) {
+        self.urls.push_back(url);
+    }
+
+    fn pop(&mut self) -> Option<Arc<Url>> {
+        self.urls.pop_front()
+    }
+
+    fn len(&self) -> usize {
+        self.urls.len()
+    }
+}
+
+// A frontier that takes URLs from each host in turn, preferring hosts that haven't been visited for at least the given
+// delay, so that a crawl doesn't hammer one host while others are waiting.
+#[derive(Clone, Debug, Default)]
+pub struct HostRoundRobinFrontier {
+    delay: Duration,
+    // The hosts with queued URLs, in the order they'll next be taken from.
+    hosts: VecDeque<String>,
+    queues: HashMap<String, VecDeque<Arc<Url>>>,
+    last_popped: HashMap<String, Instant>,
+    len: usize,
+}
+
+impl HostRoundRobinFrontier {
+    pub fn new(delay: Duration) -> HostRoundRobinFrontier {
+        HostRoundRobinFrontier {
+            delay,
+            ..HostRoundRobinFrontier::default()
+        }
+    }
+
+    fn ready_at(&self, host: &str) -> Option<Instant> {
+        self.last_popped.get(host).map(|&last_popped| last_popped + self.delay)
+    }
+}
+
+impl Frontier for HostRoundRobinFrontier {
+    fn push(&mut self, url: Arc<Url>) {
+        let host = url.host_str().unwrap_or_default().to_owned();
+        if !self.queues.contains_key(&host) {
+            self.hosts.push_back(host.clone());
+        }
+        self.queues.entry(host).or_default().push_back(url);
+        self.len += 1;
+    }
+
+    fn pop(&mut self) -> Option<Arc<Url>> {
+        // Take the first host that's ready, or else the one that'll be ready soonest.
+        let now = Instant::now();
+        let index = self.hosts.iter()
+            .position(|host| self.ready_at(host).is_none_or(|ready_at| ready_at <= now))
+            .or_else(|| (0..self.hosts.len()).min_by_key(|&i| self.ready_at(&self.hosts[i])))?;
+        let host = self.hosts.remove(index)?;
+
+        let queue = self.queues.get_mut(&host)?;
+        let url = queue.pop_front()?;
+        if queue.is_empty() {
+            self.queues.remove(&host);
+        } else {
+            self.hosts.push_back(host.clone());
+        }
+        self.last_popped.insert(host, now);
+        self.len -= 1;
+        Some(url)
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+}
@@ -16,7 +16,7 @@ impl<G: Generator<Yield = I, Return = R> + Unpin, I, R> Iterator for GenIter<G,
 
 pub macro gen_iter {
     ($($body:tt)*) => {
-        GenIter(|| {
+        GenIter(move || {
             $($body)*
         })
     }
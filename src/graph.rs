@@ -0,0 +1,91 @@
+use reqwest::Url;
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{normalize_percent_encoding, CrawlResult};
+
+// The damping factor of PageRank, i.e., the probability of following a link rather than jumping to a random page.
+const DAMPING_FACTOR: f64 = 0.85;
+const MAX_PAGE_RANK_ITERATIONS: usize = 100;
+// PageRank stops once no page's rank changes by more than this in an iteration.
+const PAGE_RANK_TOLERANCE: f64 = 1e-10;
+
+// Counts how many of the given pages link to each page (crawled or not), ignoring links from a page to itself. Pages
+// that were crawled but aren't linked to by any page are counted as zero.
+pub fn link_in_degrees<'a>(results: impl IntoIterator<Item = &'a CrawlResult>) -> HashMap<Url, usize> {
+    let mut in_degrees = HashMap::new();
+    for result in results {
+        in_degrees.entry(Url::clone(&result.url)).or_insert(0);
+        for link_url in page_links(result) {
+            *in_degrees.entry(link_url).or_insert(0) += 1;
+        }
+    }
+    in_degrees
+}
+
+// Ranks the given pages by PageRank over the links between them (links to pages that weren't crawled are ignored).
+// The ranks sum to 1.
+pub fn page_ranks<'a>(results: impl IntoIterator<Item = &'a CrawlResult>) -> HashMap<Url, f64> {
+    let results: Vec<_> = results.into_iter().collect();
+    let indices: HashMap<_, _> = results.iter().enumerate().map(|(i, result)| (&*result.url, i)).collect();
+    let links: Vec<Vec<usize>> = results.iter()
+        .map(|result| page_links(result).filter_map(|link_url| indices.get(&link_url).cloned()).collect())
+        .collect();
+
+    let n = results.len() as f64;
+    let mut ranks = vec![1.0 / n; results.len()];
+    for _ in 0..MAX_PAGE_RANK_ITERATIONS {
+        // Pages without links pass their rank on to every page equally.
+        let dangling_rank: f64 = links.iter().zip(&ranks).filter(|(links, _)| links.is_empty()).map(|(_, rank)| rank).sum();
+        let mut new_ranks = vec![(1.0 - DAMPING_FACTOR + DAMPING_FACTOR * dangling_rank) / n; results.len()];
+        for (i, links) in links.iter().enumerate() {
+            for &j in links {
+                new_ranks[j] += DAMPING_FACTOR * ranks[i] / links.len() as f64;
+            }
+        }
+
+        let change = ranks.iter().zip(&new_ranks).map(|(rank, new_rank)| (rank - new_rank).abs()).fold(0.0, f64::max);
+        ranks = new_ranks;
+        if change < PAGE_RANK_TOLERANCE {
+            break;
+        }
+    }
+
+    results.iter().map(|result| Url::clone(&result.url)).zip(ranks).collect()
+}
+
+// Counts the links to each external host (i.e., other than that of the first result, which is taken to be the seed)
+// across the given pages, most linked first (and then alphabetically). Pages on external hosts are ignored.
+pub fn external_domain_histogram<'a>(results: impl IntoIterator<Item = &'a CrawlResult>) -> Vec<(String, usize)> {
+    let mut results = results.into_iter().peekable();
+    let host = results.peek().map(|result| result.url.host_str().map(str::to_owned));
+    let mut counts = HashMap::new();
+    for result in results {
+        if Some(result.url.host_str().map(str::to_owned)) != host {
+            continue;
+        }
+        for link_url in page_links(result) {
+            if Some(link_url.host_str().map(str::to_owned)) != host {
+                *counts.entry(link_url.host_str().unwrap_or_default().to_owned()).or_insert(0) += 1;
+            }
+        }
+    }
+
+    let mut histogram: Vec<_> = counts.into_iter().collect();
+    histogram.sort_by(|(host1, count1), (host2, count2)| count2.cmp(count1).then_with(|| host1.cmp(host2)));
+    histogram
+}
+
+// The distinct pages that the given result links to, other than itself.
+fn page_links(result: &CrawlResult) -> impl Iterator<Item = Url> + '_ {
+    let mut seen = HashSet::new();
+    result.page.iter()
+        .flat_map(|page| &page.links)
+        .map(|link_url| {
+            let mut link_url = link_url.clone();
+            link_url.set_fragment(None);
+            normalize_percent_encoding(&mut link_url);
+            link_url
+        })
+        .filter(move |link_url| *link_url != *result.url && seen.insert(link_url.clone()))
+}
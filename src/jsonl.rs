@@ -0,0 +1,85 @@
+use reqwest::Url;
+
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, Write};
+
+use crate::CrawlResult;
+
+// Writes the given results as JSON Lines, one object per result giving its URL, status, depth, content type, size in
+// bytes, and (for HTML pages) title and links.
+pub fn write_jsonl<'a, W: Write>(results: impl IntoIterator<Item = &'a CrawlResult>, writer: W) -> io::Result<()> {
+    append_jsonl(results, &mut HashSet::new(), writer)
+}
+
+// Like `write_jsonl`, but skips results whose URLs were already written (e.g., before an interrupted crawl, as read by
+// `read_jsonl_urls`), adding the URLs of those it writes.
+pub fn append_jsonl<'a, W: Write>(
+    results: impl IntoIterator<Item = &'a CrawlResult>,
+    written_urls: &mut HashSet<Url>,
+    mut writer: W,
+) -> io::Result<()> {
+    for result in results {
+        if written_urls.contains(&*result.url) {
+            continue;
+        }
+        // NOTE: the URL must come first, for `read_jsonl_urls`.
+        let mut line = format!(
+            r#"{{"url":{},"status":{},"depth":{},"content_type":{},"byte_size":{}"#,
+            json_string(result.url.as_str()),
+            result.status.as_u16(),
+            result.depth,
+            json_string(&result.content_type),
+            result.byte_size,
+        );
+        match &result.page {
+            Some(page) => {
+                let links: Vec<_> = page.links.iter().map(|link_url| json_string(link_url.as_str())).collect();
+                let _ = write!(line, r#","title":{},"links":[{}]}}"#, json_string(&page.title), links.join(","));
+            }
+            None => line.push_str(r#","title":null,"links":[]}"#),
+        }
+        writeln!(writer, "{}", line)?;
+        written_urls.insert(Url::clone(&result.url));
+    }
+    Ok(())
+}
+
+// Reads the URLs of the results in JSON Lines written by `write_jsonl` (or `append_jsonl`). Incomplete lines (e.g., one
+// cut short by an interruption) are ignored, so their results are written again.
+// NOTE: serialized URLs never contain quotes or backslashes (which are percent-encoded), so the URL of each line can
+// be read without parsing the rest of it as JSON.
+pub fn read_jsonl_urls(reader: impl BufRead) -> io::Result<HashSet<Url>> {
+    let mut urls = HashSet::new();
+    for line in reader.lines() {
+        let line = line?;
+        if !line.ends_with('}') {
+            continue;
+        }
+        let url = line.strip_prefix(r#"{"url":""#)
+            .and_then(|rest| rest.split('"').next())
+            .and_then(|url| url.parse().ok());
+        urls.extend(url);
+    }
+    Ok(urls)
+}
+
+fn json_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
@@ -1,159 +1,5625 @@
-#![feature(decl_macro)]
-#![feature(generators, generator_trait)]
+#![cfg_attr(feature = "nightly", feature(decl_macro))]
+#![cfg_attr(feature = "nightly", feature(generators, generator_trait))]
+// NOTE: `#[derive(Fail)]` expands to impls inside a constant, which newer compilers warn about.
+#![allow(non_local_definitions)]
 
+mod cache;
+mod csv;
+mod diff;
+mod fetcher;
+mod frontier;
+#[cfg(feature = "nightly")]
 mod generators;
+mod graph;
+mod jsonl;
+mod mirror;
+mod parse;
+mod robots;
+mod sitemap;
+mod streaming;
+mod visited;
+#[cfg(test)]
+mod test_server;
+
+use encoding_rs::{Encoding, UTF_8};
 
 use failure::Fail;
 
-use reqwest::{self, IntoUrl, StatusCode, Url};
-use reqwest::header::{self, HeaderValue};
+use log::{debug, info, warn};
+
+use reqwest::{self, Client, IntoUrl, Method, RedirectPolicy, RequestBuilder, Response, StatusCode, Url, Version};
+use reqwest::header::{self, HeaderMap, HeaderName, HeaderValue};
 
 use select::document::Document;
-use select::predicate::{Attr, Class, Name, Predicate};
+use select::node::Node;
+use select::predicate::{Element, Name, Predicate};
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::iter;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::ops::Deref;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use std::collections::{HashSet, VecDeque};
+use url::Position;
 
+use crate::cache::CachedResponse;
+#[cfg(feature = "nightly")]
 use crate::generators::gen_iter;
+use crate::robots::Robots;
+
+pub use crate::cache::ResponseCache;
+pub use crate::csv::write_csv;
+pub use crate::diff::{diff, CrawlDiff};
+pub use crate::fetcher::{FetchedContent, Fetcher};
+pub use crate::frontier::{FifoFrontier, Frontier, HostRoundRobinFrontier};
+pub use crate::graph::{external_domain_histogram, link_in_degrees, page_ranks};
+pub use crate::jsonl::{append_jsonl, read_jsonl_urls, write_jsonl};
+pub use crate::sitemap::write_sitemap;
+pub use crate::visited::{HashVisitedSet, VisitedSet};
+
+// NOTE: the crawl moves each page's info into its result rather than cloning it, so its fields are kept plain rather
+// than in `Arc`s, which would make cloning results cheaper but editing them awkward. Callers that clone results a lot
+// can share them in an `Arc` instead.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct WebPageInfo {
+    pub title: String,
+    pub links: Vec<Url>,
+    pub headings: Vec<(u8, String)>,
+    pub resource_hints: Vec<ResourceHint>,
+    // The images referenced by `<img>` (and `<picture>`) elements, including every candidate of a `srcset`.
+    pub images: Vec<Url>,
+    // Every `<link>` element, as its `rel` attribute (empty if missing) and `href`.
+    pub link_elements: Vec<(String, Url)>,
+    // The content types that links declare via their `type` attribute, where they do (e.g., `application/pdf`).
+    pub link_types: Vec<(Url, String)>,
+    // Each of `links`, in the same order, with whether it's internal. When crawling, that's relative to the seed;
+    // otherwise, it's relative to the page itself.
+    pub link_details: Vec<Link>,
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Link {
+    pub url: Url,
+    // Whether the link is to the same host as the seed.
+    pub internal: bool,
+}
+
+impl Link {
+    fn all_relative_to(links: &[Url], seed: &Url, treat_www_as_same: bool) -> Vec<Link> {
+        let seed_host = host_key(seed, treat_www_as_same);
+        links.iter().map(|url| Link {
+            url: url.clone(),
+            internal: host_key(url, treat_www_as_same) == seed_host,
+        }).collect()
+    }
+}
+
+impl WebPageInfo {
+    // Like `==`, but ignores the order of links, so that pages linking to the same set of pages compare equal.
+    pub fn semantically_equal(&self, other: &WebPageInfo) -> bool {
+        self.title == other.title &&
+            self.headings == other.headings &&
+            self.resource_hints == other.resource_hints &&
+            self.images == other.images &&
+            self.link_elements == other.link_elements &&
+            self.link_types == other.link_types &&
+            self.link_details.iter().collect::<HashSet<_>>() == other.link_details.iter().collect::<HashSet<_>>() &&
+            self.links.len() == other.links.len() &&
+            self.links.iter().collect::<HashSet<_>>() == other.links.iter().collect::<HashSet<_>>()
+    }
+}
+
+// A one-line summary, e.g. `"Home" (42 links)`.
+impl fmt::Display for WebPageInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "\"{}\" ({} link{})", self.title, self.links.len(), if self.links.len() == 1 { "" } else { "s" })
+    }
+}
 
+// A resource declared by `<link rel="preload">` or `<link rel="prefetch">`.
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
-struct WebPageInfo {
-    title: String,
-    links: Vec<Url>,
+pub struct ResourceHint {
+    pub url: Url,
+    pub rel: String,
+    // The `as` attribute, giving the kind of resource (e.g., `script`).
+    pub as_type: Option<String>,
+}
+
+// NOTE: the URL is the one the crawler queued and marked as visited, rather than a copy of it.
+#[derive(Clone, Debug)]
+pub struct CrawlResult {
+    pub url: Arc<Url>,
+    pub status: StatusCode,
+    // How many links from the seed the page was found by (following the first links found to it).
+    pub depth: usize,
+    // Only HTML pages are parsed; other resources are merely recorded.
+    pub page: Option<WebPageInfo>,
+    pub content_type: String,
+    pub byte_size: usize,
+    pub http_version: Version,
+    pub headers: HeaderMap,
+    pub raw_html: Option<String>,
+    pub timing: Option<Timing>,
+    // Each redirect followed to get to the page, as the URL redirected from and the status of the redirect, in order.
+    // NOTE: redirects that a custom client follows itself aren't seen by the crawler, so aren't recorded.
+    pub redirect_chain: Vec<(Url, StatusCode)>,
+    // When the page was last modified, as given by the `Last-Modified` header or else (for HTML pages) the
+    // `article:modified_time` meta property.
+    pub last_modified: Option<time::Timespec>,
+    // Whether the page was fetched over HTTPS.
+    pub secure: bool,
+    // The insecure (plain HTTP) subresources of a page fetched over HTTPS, e.g. images and scripts.
+    pub mixed_content: Vec<Url>,
+    // Whether the page looks like an error page despite its successful status, in which case its links weren't
+    // followed.
+    pub soft_404: bool,
+    // How many distinct links the page has, and how many of them were queued to be crawled (i.e., not filtered out or
+    // already queued or visited).
+    pub links_total: usize,
+    pub links_followed: usize,
+}
+
+// A one-line summary, e.g. `https://example.com/ — "Home" (42 links)`, or `https://example.com/a.pdf — application/pdf
+// (1024 bytes)` for a resource that wasn't parsed.
+impl fmt::Display for CrawlResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.page {
+            Some(page) => write!(f, "{} — {}", self.url, page),
+            None => write!(f, "{} — {} ({} bytes)", self.url, self.content_type, self.byte_size),
+        }
+    }
+}
+
+// A page that couldn't be fetched, as yielded by `Crawler::crawl_attempts`.
+#[derive(Debug)]
+pub struct CrawlFailure {
+    pub url: Arc<Url>,
+    pub error: FetchWebPageError,
+}
+
+// The error is boxed, since it's much larger than most results.
+pub type CrawlAttempt = Result<CrawlResult, Box<CrawlFailure>>;
+
+// When each stage of fetching a page was done, relative to the start of the request.
+// NOTE: reqwest doesn't expose the time spent resolving and connecting, so `dns` and `connect` are only estimates: they
+// come from a separate probe connection opened just before the request, not from the request itself, which makes its
+// own connection (or reuses a pooled one, taking no time to connect at all). `ttfb` and `total` are the probe's connect
+// time plus the times measured for the request itself.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Timing {
+    pub dns: Duration,
+    pub connect: Duration,
+    pub ttfb: Duration,
+    pub total: Duration,
+}
+
+struct FetchedResource {
+    status: StatusCode,
+    content_type: String,
+    byte_size: usize,
+    http_version: Version,
+    headers: HeaderMap,
+    // How long it took to receive the response headers, and the whole body.
+    headers_time: Duration,
+    total_time: Duration,
+    // How long it took to resolve the host and connect to it, if measured.
+    connection_times: Option<(Duration, Duration)>,
+    // The redirects followed by the crawler to get here.
+    redirect_chain: Vec<(Url, StatusCode)>,
+    // The raw `Content-Location` header, if any.
+    content_location: Option<String>,
+    // The body of a resource other than an HTML page, if asked to keep it.
+    body: Option<Vec<u8>>,
+    html: Option<FetchedPage>,
+}
+
+// How to fetch resources, beyond the request itself.
+#[derive(Clone, Copy, Debug, Default)]
+struct FetchOptions<'a> {
+    extra_content_types: &'a [String],
+    // Unless given, any successful (2xx) status is accepted.
+    accepted_statuses: Option<&'a [StatusCode]>,
+    // Whether to keep the bodies of resources other than HTML pages, rather than discarding them.
+    keep_bodies: bool,
+    max_body_size: Option<usize>,
+    // Whether not to parse HTML pages into documents, in which case an empty document stands in for each.
+    skip_documents: bool,
+}
+
+// A writer that counts the bytes written to it, failing once they would exceed the limit (if any).
+struct LimitedWriter<W> {
+    inner: W,
+    written: usize,
+    limit: Option<usize>,
+    exceeded: bool,
+}
+
+impl<W: Write> Write for LimitedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.limit.is_some_and(|limit| self.written + buf.len() > limit) {
+            self.exceeded = true;
+            return Err(io::Error::other("body too large"));
+        }
+        let len = self.inner.write(buf)?;
+        self.written += len;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl FetchedResource {
+    fn timing(&self) -> Option<Timing> {
+        let (dns, connect) = self.connection_times?;
+        Some(Timing {
+            dns,
+            connect,
+            ttfb: connect + self.headers_time,
+            total: connect + self.total_time,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FetchedPage {
+    // The URL of the response, which differs from the one requested if the client followed redirects.
+    pub final_url: Url,
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: String,
+    pub document: Document,
+}
+
+// The broken internal links of a site, as found by `Crawler::audit`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct LinkReport {
+    // In the order they were found.
+    pub broken_links: Vec<BrokenLink>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BrokenLink {
+    pub url: Url,
+    pub status: StatusCode,
+    // The pages linking to it, in the order they were crawled.
+    pub referrers: Vec<Url>,
+}
+
+// The links of a single page and the status of each, as found by `Crawler::check_page`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PageCheck {
+    // The URL of the page, after any redirects.
+    pub url: Url,
+    // Each distinct link of the page (ignoring fragments) that the crawler can fetch, in the order found.
+    pub links: Vec<LinkCheck>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LinkCheck {
+    pub url: Url,
+    // The status of the response, or none if there wasn't one (e.g., because the host couldn't be reached).
+    // Links of a scheme with its own fetcher are checked by fetching them, and have an OK status if that succeeds.
+    // NOTE: redirects aren't followed, so are reported as such (unless an injected client follows them itself, in which
+    // case the status is that of wherever the link finally leads).
+    pub status: Option<StatusCode>,
+}
+
+#[derive(Debug, Fail)]
+pub enum CrawlError {
+    #[fail(display = "invalid seed URL: {}", _0)]
+    InvalidSeed(#[cause] reqwest::Error),
+    #[fail(display = "login failed: {}", _0)]
+    LoginFailed(#[cause] reqwest::Error),
+    #[fail(display = "failed to fetch seed: {}", _0)]
+    SeedFailed(#[cause] FetchWebPageError),
+}
+
+// A form to post before crawling, e.g. to log in. Any cookies the server sets are sent with every request of the crawl.
+#[derive(Clone, Debug)]
+pub struct LoginConfig {
+    pub url: Url,
+    pub form_fields: Vec<(String, String)>,
+}
+
+// Ways of spotting "soft 404s": error pages served with a successful status. A page matching any of them is flagged.
+#[derive(Clone, Debug, Default)]
+pub struct Soft404Config {
+    // The most text (in bytes, ignoring surrounding whitespace) that the body of a page may have to count as empty.
+    pub max_body_text_len: Option<usize>,
+    // Phrases (e.g., "page not found") to look for in the title or body text, ignoring case.
+    pub patterns: Vec<String>,
+    // The hashes (from `Soft404Config::hash_page`) of known error pages.
+    pub error_page_hashes: Vec<u64>,
+}
+
+impl Soft404Config {
+    // Hashes the HTML of an error page, for `error_page_hashes`.
+    // NOTE: the hash may differ between versions of Rust, so should be computed at runtime rather than hard-coded.
+    pub fn hash_page(html: &str) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        html.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn matches(&self, html: &str, doc: &Document, page: &WebPageInfo) -> bool {
+        let body_text = doc.find(Name("body")).next().map(|body| body.text()).unwrap_or_default();
+        let body_text = body_text.trim();
+        if self.max_body_text_len.is_some_and(|max_len| body_text.len() <= max_len) {
+            return true;
+        }
+
+        let title = page.title.to_lowercase();
+        let body_text = body_text.to_lowercase();
+        let matches_pattern = self.patterns.iter().any(|pattern| {
+            let pattern = pattern.to_lowercase();
+            title.contains(&pattern) || body_text.contains(&pattern)
+        });
+        matches_pattern || self.error_page_hashes.contains(&Soft404Config::hash_page(html))
+    }
+}
+
+// How to treat a trailing slash at the end of the path of the seed and each link, so that a site that's inconsistent
+// about them has each page visited only once.
+// NOTE: paths whose last segment has a file extension (e.g., `/a/b.html`) are never given a trailing slash.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum TrailingSlash {
+    // Leave paths as they are, so that `/a` and `/a/` are different pages.
+    #[default]
+    Preserve,
+    // Add a trailing slash to paths that look like directories (e.g., `/a` becomes `/a/`).
+    AddToDirectories,
+    // Remove any trailing slash (e.g., `/a/` becomes `/a`), other than from the root.
+    Strip,
+}
+
+impl TrailingSlash {
+    fn apply(self, url: &mut Url) {
+        if url.cannot_be_a_base() {
+            return;
+        }
+        let path = match self {
+            TrailingSlash::Preserve => return,
+            TrailingSlash::AddToDirectories => {
+                let last_segment = url.path().rsplit('/').next().unwrap_or_default();
+                if last_segment.is_empty() || last_segment.contains('.') {
+                    return;
+                }
+                format!("{}/", url.path())
+            }
+            TrailingSlash::Strip => {
+                if url.path() == "/" || !url.path().ends_with('/') {
+                    return;
+                }
+                url.path().trim_end_matches('/').to_owned()
+            }
+        };
+        url.set_path(&path);
+    }
+}
+
+// How to extract the links (and such) of each page.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum LinkExtraction {
+    // Parse each page into a document, and extract everything about it from that.
+    #[default]
+    FullDom,
+    // Tokenize each page, extracting only its title and links (and their types), which takes much less memory for big
+    // pages. Everything else about the page is left empty, and the document isn't yielded (by
+    // `Crawler::crawl_with_documents`).
+    Streaming,
+}
+
+// A handle for stopping crawls from elsewhere (e.g., another thread).
+// NOTE: this counts the times it's been cancelled, so that each crawl can tell whether it's been cancelled since it
+// started, and later crawls aren't affected.
+#[derive(Clone, Debug, Default)]
+pub struct CancelHandle(Arc<AtomicUsize>);
+
+impl CancelHandle {
+    // Stops every current crawl of the crawler (and its clones) before it fetches another page. Crawls started
+    // afterwards run as usual.
+    pub fn cancel(&self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn cancels(&self) -> usize {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+// A crawl in progress, which yields its results as it goes.
+#[derive(Debug)]
+pub struct Crawl<I> {
+    results: I,
+    frontier_len: FrontierLen,
+    dropped_urls: Arc<AtomicUsize>,
+}
+
+impl<I> Crawl<I> {
+    // The number of URLs queued but not yet fetched.
+    pub fn frontier_len(&self) -> usize {
+        self.frontier_len.get()
+    }
+
+    // A handle for checking the number of queued URLs from elsewhere (e.g., another thread).
+    pub fn frontier_len_handle(&self) -> FrontierLen {
+        self.frontier_len.clone()
+    }
+
+    // The number of times so far that a link was dropped because the frontier was full (see
+    // `CrawlerBuilder::max_frontier`). A link that's dropped each time it's found is counted each time.
+    pub fn dropped_urls(&self) -> usize {
+        self.dropped_urls.load(Ordering::SeqCst)
+    }
+}
+
+impl<I: Iterator<Item = CrawlAttempt>> Crawl<I> {
+    // Drops the pages that couldn't be fetched, as `crawl` itself does.
+    pub fn successes_only(self) -> Crawl<impl Iterator<Item = CrawlResult>> {
+        Crawl {
+            results: self.results.filter_map(Result::ok),
+            frontier_len: self.frontier_len,
+            dropped_urls: self.dropped_urls,
+        }
+    }
+}
+
+impl<I: Iterator> Iterator for Crawl<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        self.results.next()
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct FrontierLen(Arc<AtomicUsize>);
+
+impl FrontierLen {
+    pub fn get(&self) -> usize {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    fn set(&self, len: usize) {
+        self.0.store(len, Ordering::SeqCst);
+    }
 }
 
 #[derive(Debug, Fail)]
-enum FetchWebPageError {
+pub enum FetchWebPageError {
     #[fail(display = "{}", _0)]
     HttpError(#[cause] reqwest::Error),
+    #[fail(display = "connect timeout: {}", _0)]
+    ConnectTimeout(#[cause] reqwest::Error),
+    #[fail(display = "DNS error: {}", _0)]
+    DnsError(#[cause] reqwest::Error),
+    #[fail(display = "connect error: {}", _0)]
+    ConnectError(#[cause] reqwest::Error),
+    #[fail(display = "TLS error: {}", _0)]
+    TlsError(#[cause] reqwest::Error),
     #[fail(display = "bad HTTP status: {}", _0)]
     BadHttpStatus(StatusCode),
+    #[fail(display = "redirected to {}", _1)]
+    Redirect(StatusCode, String),
     #[fail(display = "missing HTTP content type")]
     MissingContentType,
     #[fail(display = "bad HTTP content type: {:?}", _0)]
     BadContentType(HeaderValue),
     #[fail(display = "text decoding error: {:?}", _0)]
     TextDecodeError(#[cause] reqwest::Error),
+    #[fail(display = "body larger than {} bytes", _0)]
+    BodyTooLarge(usize),
+    #[fail(display = "unsupported charset: {}", _0)]
+    UnsupportedCharset(String),
+    // For custom fetchers, e.g. of local files.
+    #[fail(display = "I/O error: {}", _0)]
+    Io(#[cause] io::Error),
 }
 
 #[derive(Debug, Fail)]
 enum GetWebPageInfoError {
     #[fail(display = "document has no title")]
     NoTitle,
+    #[fail(display = "parsing took longer than {:?}", _0)]
+    ParseTimeout(Duration),
+}
+
+impl FetchWebPageError {
+    // Whether the error suggests that the server is struggling, so we should back off.
+    fn is_overload(&self) -> bool {
+        match self {
+            FetchWebPageError::HttpError(_) |
+            FetchWebPageError::ConnectTimeout(_) |
+            FetchWebPageError::ConnectError(_) => true,
+            FetchWebPageError::BadHttpStatus(status) => *status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error(),
+            _ => false,
+        }
+    }
+
+    // Whether the error suggests that the host is down.
+    fn is_host_failure(&self) -> bool {
+        match self {
+            FetchWebPageError::HttpError(_) |
+            FetchWebPageError::ConnectTimeout(_) |
+            FetchWebPageError::DnsError(_) |
+            FetchWebPageError::ConnectError(_) |
+            FetchWebPageError::TlsError(_) => true,
+            FetchWebPageError::BadHttpStatus(status) => status.is_server_error(),
+            _ => false,
+        }
+    }
+}
+
+// Fetches the HTML page at the given URL, checking the status and content type of the response.
+pub fn fetch(url: impl IntoUrl, client: &Client) -> Result<FetchedPage, FetchWebPageError> {
+    let resource = fetch_web_page(client.get(url), &FetchOptions::default())?;
+    match resource.html {
+        Some(html) => Ok(html),
+        None => match resource.headers.get(header::CONTENT_TYPE) {
+            Some(content_type) => Err(FetchWebPageError::BadContentType(content_type.clone())),
+            None => Err(FetchWebPageError::MissingContentType),
+        },
+    }
+}
+
+fn fetch_web_page(request: RequestBuilder, options: &FetchOptions) -> Result<FetchedResource, FetchWebPageError> {
+    let start = Instant::now();
+    let resp = request.send().map_err(classify_send_error)?;
+    read_web_page(resp, start, options)
 }
 
-fn fetch_web_page(url: impl IntoUrl) -> Result<Document, FetchWebPageError> {
-    let mut resp = reqwest::get(url).map_err(FetchWebPageError::HttpError)?;
+// Checks and reads the response to a request sent at the given time, as `fetch_web_page` does.
+fn read_web_page(mut resp: Response, start: Instant, options: &FetchOptions) -> Result<FetchedResource, FetchWebPageError> {
+    let headers_time = start.elapsed();
+    debug!("fetched {} with status {}", resp.url(), resp.status());
+
+    // This only happens if the client doesn't follow redirects itself.
+    if resp.status().is_redirection() {
+        if let Some(location) = resp.headers().get(header::LOCATION).and_then(|location| location.to_str().ok()) {
+            return Err(FetchWebPageError::Redirect(resp.status(), location.to_owned()));
+        }
+    }
 
-    if !resp.status().is_success() {
+    let accepted = match options.accepted_statuses {
+        Some(accepted_statuses) => accepted_statuses.contains(&resp.status()),
+        None => resp.status().is_success(),
+    };
+    if !accepted {
         return Err(FetchWebPageError::BadHttpStatus(resp.status()));
     }
 
-    if let Some(content_type) = resp.headers().get(header::CONTENT_TYPE) {
-        if let Ok("text/html") = content_type.to_str() {
-            return Err(FetchWebPageError::BadContentType(content_type.clone()));
+    let content_location = resp.headers().get(header::CONTENT_LOCATION)
+        .and_then(|location| location.to_str().ok())
+        .map(str::to_owned);
+    let content_type = match resp.headers().get(header::CONTENT_TYPE) {
+        // Ignore any parameters (e.g., `charset`) after the MIME type itself.
+        Some(content_type) => content_type.to_str().ok()
+            .and_then(|s| s.split(';').next())
+            .map(|mime| mime.trim().to_ascii_lowercase()),
+        None => return Err(FetchWebPageError::MissingContentType),
+    };
+    let content_type = match content_type {
+        Some(ref mime) if mime == "text/html" => mime.clone(),
+        Some(ref mime) if options.extra_content_types.iter().any(|pattern| mime_matches(pattern, mime)) => {
+            let (byte_size, body) = if options.keep_bodies {
+                let mut body = vec![];
+                let byte_size = read_body(&mut resp, options, &mut body, FetchWebPageError::HttpError)?;
+                (byte_size, Some(body))
+            } else {
+                (read_body(&mut resp, options, io::sink(), FetchWebPageError::HttpError)?, None)
+            };
+            return Ok(FetchedResource {
+                status: resp.status(),
+                content_type: mime.clone(),
+                byte_size,
+                http_version: resp.version(),
+                headers: resp.headers().clone(),
+                headers_time,
+                total_time: start.elapsed(),
+                connection_times: None,
+                redirect_chain: vec![],
+                content_location,
+                body,
+                html: None,
+            });
+        }
+        _ => return Err(FetchWebPageError::BadContentType(resp.headers()[header::CONTENT_TYPE].clone())),
+    };
+
+    let mut bytes = vec![];
+    let byte_size = read_body(&mut resp, options, &mut bytes, FetchWebPageError::TextDecodeError)?;
+    let body = decode_body(&bytes, resp.headers().get(header::CONTENT_TYPE).and_then(|content_type| content_type.to_str().ok()))?;
+    let total_time = start.elapsed();
+    // NOTE: 'select' may not be the most robust library, since it doesn't even return potential HTML parsing errors!
+    let document = if options.skip_documents { Document::from("") } else { (&*body).into() };
+    Ok(FetchedResource {
+        status: resp.status(),
+        content_type,
+        byte_size,
+        http_version: resp.version(),
+        headers: resp.headers().clone(),
+        headers_time,
+        total_time,
+        connection_times: None,
+        redirect_chain: vec![],
+        content_location,
+        body: None,
+        html: Some(FetchedPage {
+            final_url: resp.url().clone(),
+            status: resp.status(),
+            headers: resp.headers().clone(),
+            body,
+            document,
+        }),
+    })
+}
+
+// Checks and parses a resource fetched by a custom fetcher, much as `fetch_web_page` does a response.
+fn fetched_content(
+    url: &Url,
+    content: FetchedContent,
+    options: &FetchOptions,
+    start: Instant,
+) -> Result<FetchedResource, FetchWebPageError> {
+    let FetchedContent { content_type: full_content_type, body: bytes } = content;
+    let content_type = full_content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    let is_html = content_type == "text/html";
+    if !is_html && !options.extra_content_types.iter().any(|pattern| mime_matches(pattern, &content_type)) {
+        let content_type = HeaderValue::from_str(&full_content_type).map_err(|_| FetchWebPageError::MissingContentType)?;
+        return Err(FetchWebPageError::BadContentType(content_type));
+    }
+    if let Some(max_body_size) = options.max_body_size.filter(|&max_body_size| bytes.len() > max_body_size) {
+        return Err(FetchWebPageError::BodyTooLarge(max_body_size));
+    }
+
+    let mut headers = HeaderMap::new();
+    if let Ok(content_type) = HeaderValue::from_str(&full_content_type) {
+        headers.insert(header::CONTENT_TYPE, content_type);
+    }
+    let byte_size = bytes.len();
+    let total_time = start.elapsed();
+    let (body, html) = if is_html {
+        let body = decode_body(&bytes, Some(&full_content_type))?;
+        let document = if options.skip_documents { Document::from("") } else { (&*body).into() };
+        (None, Some(FetchedPage {
+            final_url: url.clone(),
+            status: StatusCode::OK,
+            headers: headers.clone(),
+            body,
+            document,
+        }))
+    } else {
+        (if options.keep_bodies { Some(bytes) } else { None }, None)
+    };
+    Ok(FetchedResource {
+        status: StatusCode::OK,
+        content_type,
+        byte_size,
+        http_version: Version::default(),
+        headers,
+        headers_time: total_time,
+        total_time,
+        connection_times: None,
+        redirect_chain: vec![],
+        content_location: None,
+        body,
+        html,
+    })
+}
+
+// Decodes the body of an HTML page as `Response::text` would, defaulting to UTF-8 (with any byte order mark taking
+// precedence). A declared charset that we don't know is an error, rather than a guess at UTF-8.
+fn decode_body(bytes: &[u8], content_type: Option<&str>) -> Result<String, FetchWebPageError> {
+    let encoding = match content_type.and_then(content_type_charset) {
+        Some(charset) if Encoding::for_bom(bytes).is_none() => Encoding::for_label(charset.as_bytes())
+            .ok_or_else(|| FetchWebPageError::UnsupportedCharset(charset.to_owned()))?,
+        _ => UTF_8,
+    };
+    Ok(encoding.decode(bytes).0.into_owned())
+}
+
+// Reads the body as it streams in, counting the bytes received rather than trusting `Content-Length` (which chunked
+// responses don't have anyway).
+fn read_body(
+    resp: &mut Response,
+    options: &FetchOptions,
+    body: impl Write,
+    read_error: fn(reqwest::Error) -> FetchWebPageError,
+) -> Result<usize, FetchWebPageError> {
+    let mut writer = LimitedWriter {
+        inner: body,
+        written: 0,
+        limit: options.max_body_size,
+        exceeded: false,
+    };
+    match resp.copy_to(&mut writer) {
+        Ok(_) => Ok(writer.written),
+        Err(_) if writer.exceeded => Err(FetchWebPageError::BodyTooLarge(options.max_body_size.unwrap_or_default())),
+        Err(err) => Err(read_error(err)),
+    }
+}
+
+// The `charset` parameter of the given `Content-Type`, if any.
+fn content_type_charset(content_type: &str) -> Option<&str> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let mut name_value = param.splitn(2, '=');
+        if !name_value.next()?.trim().eq_ignore_ascii_case("charset") {
+            return None;
+        }
+        Some(name_value.next()?.trim().trim_matches('"'))
+    })
+}
+
+fn classify_send_error(err: reqwest::Error) -> FetchWebPageError {
+    // The client's overall timeout surfaces as a plain I/O error, whereas a connect timeout comes from the connector.
+    if err.is_timeout() && err.get_ref().is_some_and(|inner| !inner.is::<io::Error>()) {
+        return FetchWebPageError::ConnectTimeout(err);
+    }
+
+    // Failures to connect are wrapped by hyper, with the I/O error from the connector as their cause.
+    let connect_error = err.get_ref()
+        .and_then(|inner| inner.source())
+        .and_then(|cause| cause.downcast_ref::<io::Error>());
+    match connect_error {
+        Some(io_err) if io_err.get_ref().is_some_and(|inner| inner.is::<native_tls::Error>()) => {
+            FetchWebPageError::TlsError(err)
+        }
+        // Errors from the socket itself (e.g., connection refused) come from the OS, whereas failures to resolve the
+        // host come from `getaddrinfo`, which std reports with a message of its own.
+        Some(io_err) if io_err.raw_os_error().is_some() => FetchWebPageError::ConnectError(err),
+        Some(io_err) if io_err.get_ref().is_some() && io_err.kind() != io::ErrorKind::InvalidInput => {
+            FetchWebPageError::DnsError(err)
         }
+        _ => FetchWebPageError::HttpError(err),
+    }
+}
+
+// Matches a MIME type against a pattern such as `application/pdf` or `image/*`.
+fn mime_matches(pattern: &str, mime: &str) -> bool {
+    if pattern.ends_with("/*") {
+        mime.split('/').next() == pattern.split('/').next()
     } else {
-        return Err(FetchWebPageError::MissingContentType);
+        pattern.eq_ignore_ascii_case(mime)
     }
+}
 
-    let text = resp.text().map_err(FetchWebPageError::TextDecodeError)?;
-    // NOTE: 'select' may not be the most robust library, since it doesn't even return potential HTML parsing errors!
-    let doc = (&*text).into();
-    Ok(doc)
+// Extracts the links of the given HTML, resolved as for `WebPageInfo::links`, without requiring a title (or anything
+// else) of the page.
+pub fn extract_links(base: &Url, html: &str) -> Vec<Url> {
+    let doc = Document::from(html);
+    let base = document_base(base, &doc);
+    distinct_links(&link_nodes(&base, &doc, DEFAULT_LINK_ATTRIBUTES))
+}
+
+// Resolve relative links against the document's `<base>` element if it has one, or else its own URL.
+fn document_base(url: &Url, doc: &Document) -> Url {
+    doc.find(Name("base"))
+        .filter_map(|n| n.attr("href"))
+        .next()
+        .and_then(|href| url.join(href).ok())
+        .unwrap_or_else(|| url.clone())
+}
+
+// The attributes of links that give their URLs, unless configured otherwise.
+const DEFAULT_LINK_ATTRIBUTES: &[&str] = &["href"];
+
+fn link_nodes<'a>(base: &Url, doc: &'a Document, link_attributes: &[impl AsRef<str>]) -> Vec<(Url, Node<'a>)> {
+    doc.find(Name("a").or(Name("area"))).flat_map(|n| {
+        link_attributes.iter().filter_map(move |attribute| {
+            // Ignore links without the attribute or with invalid URLs.
+            let url = n.attr(attribute.as_ref()).and_then(|s| base.join(s).ok())?;
+            Some((url, n))
+        })
+    }).collect()
+}
+
+fn distinct_links(link_nodes: &[(Url, Node<'_>)]) -> Vec<Url> {
+    let mut links_seen = HashSet::new();
+    link_nodes.iter().map(|(url, _)| url.clone()).filter(|url| {
+        // Only keep the first occurrence of each link.
+        links_seen.insert(url.clone())
+    }).collect()
+}
+
+// Everything the crawl gets from the document of a page.
+struct DocumentInfo {
+    page: WebPageInfo,
+    mixed_content: Vec<Url>,
+    soft_404: bool,
+    last_modified: Option<time::Timespec>,
+}
+
+fn document_info(
+    url: &Url,
+    html: &str,
+    doc: &Document,
+    link_attributes: &[String],
+    soft_404: Option<&Soft404Config>,
+) -> Result<DocumentInfo, GetWebPageInfoError> {
+    let page = get_web_page_info_with(url, doc, link_attributes)?;
+    Ok(DocumentInfo {
+        mixed_content: if url.scheme() == "https" { find_mixed_content(url, doc, &page) } else { vec![] },
+        soft_404: soft_404.is_some_and(|soft_404| soft_404.matches(html, doc, &page)),
+        last_modified: meta_last_modified(doc),
+        page,
+    })
+}
+
+// Like `document_info`, but parses the page itself, giving up if that (along with the rest) takes longer than the
+// given timeout.
+// NOTE: documents can't be sent between threads, so all of it happens on a thread of its own. That thread stops parsing
+// soon after the timeout, but if the parse itself finishes in time, the rest (extracting links and so on) is left to
+// finish in the background once the timeout passes, and its result is thrown away.
+fn document_info_within(
+    url: &Url,
+    html: &str,
+    link_attributes: &[String],
+    soft_404: Option<&Soft404Config>,
+    timeout: Duration,
+) -> Result<DocumentInfo, GetWebPageInfoError> {
+    let deadline = Instant::now() + timeout;
+    let (sender, receiver) = mpsc::channel();
+    let url = url.clone();
+    let html = html.to_owned();
+    let link_attributes = link_attributes.to_vec();
+    let soft_404 = soft_404.cloned();
+    thread::spawn(move || {
+        let info = parse::parse_document_by(&html, deadline)
+            .ok_or(GetWebPageInfoError::ParseTimeout(timeout))
+            .and_then(|doc| document_info(&url, &html, &doc, &link_attributes, soft_404.as_ref()));
+        let _ = sender.send(info);
+    });
+    receiver.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+        .unwrap_or(Err(GetWebPageInfoError::ParseTimeout(timeout)))
+}
+
+// Only tests extract pages with the default link attributes, since the crawler always passes its own.
+#[cfg(test)]
+fn get_web_page_info(url: &Url, doc: &Document) -> Result<WebPageInfo, GetWebPageInfoError> {
+    get_web_page_info_with(url, doc, DEFAULT_LINK_ATTRIBUTES)
 }
 
-fn get_web_page_info(doc: Document) -> Result<WebPageInfo, GetWebPageInfoError> {
+// Like `get_web_page_info`, but takes the URLs of links from the given attributes (any of which a link may have).
+fn get_web_page_info_with(
+    url: &Url,
+    doc: &Document,
+    link_attributes: &[impl AsRef<str>],
+) -> Result<WebPageInfo, GetWebPageInfoError> {
     let title_node = doc.find(Name("title")).next().ok_or(GetWebPageInfoError::NoTitle)?;
     let title = title_node.text().trim().into();
 
-    let anchor_nodes = doc.find(Name("a"));
-    let links = anchor_nodes.filter_map(|n| {
-        // Ignore anchors without `href` attribute or with invalid URLs.
-        n.attr("href").and_then(|s| s.parse().ok())
+    let base = document_base(url, doc);
+    let link_nodes = link_nodes(&base, doc, link_attributes);
+    let links = distinct_links(&link_nodes);
+    let link_types = link_nodes.iter().filter_map(|(url, n)| {
+        // Ignore any parameters, as for the `Content-Type` header.
+        let mime = n.attr("type")?.split(';').next()?.trim().to_ascii_lowercase();
+        Some((url.clone(), mime))
+    }).collect();
+
+    let headings = doc.find(Element).filter_map(|n| {
+        let level = match n.name()? {
+            "h1" => 1,
+            "h2" => 2,
+            "h3" => 3,
+            "h4" => 4,
+            "h5" => 5,
+            "h6" => 6,
+            _ => return None,
+        };
+        Some((level, n.text().trim().to_owned()))
     }).collect();
 
+    let resource_hints = doc.find(Name("link")).filter_map(|n| {
+        let rel = n.attr("rel")?
+            .split_ascii_whitespace()
+            .map(str::to_ascii_lowercase)
+            .find(|rel| rel == "preload" || rel == "prefetch")?;
+        let url = base.join(n.attr("href")?).ok()?;
+        Some(ResourceHint {
+            url,
+            rel,
+            as_type: n.attr("as").map(str::to_owned),
+        })
+    }).collect();
+
+    let mut images_seen = HashSet::new();
+    let images = doc.find(Name("img").or(Name("source"))).flat_map(|n| {
+        // Only images have a `src`; a `<source>` can also belong to `<audio>` or `<video>`, but then it has no `srcset`.
+        let src = if n.is(Name("img")) { n.attr("src") } else { None };
+        src.into_iter().chain(n.attr("srcset").map_or_else(Vec::new, parse_srcset))
+    }).filter_map(|s| base.join(s).ok()).filter(|url| {
+        images_seen.insert(url.clone())
+    }).collect();
+
+    let link_elements = doc.find(Name("link")).filter_map(|n| {
+        let url = base.join(n.attr("href")?).ok()?;
+        Some((n.attr("rel").unwrap_or("").trim().to_owned(), url))
+    }).collect();
+
+    let link_details = Link::all_relative_to(&links, url, false);
     Ok(WebPageInfo {
         title,
         links,
+        headings,
+        resource_hints,
+        images,
+        link_elements,
+        link_types,
+        link_details,
     })
 }
 
-// NOTE: ideally we'd make this a stream of futures (`FuturesUnordered`) and leverage parallelism, but this would take a lot more effort and care.
-// NOTE: this could be expanded to use a library like 'robotparser' to respect websites that use a `robots.txt` to stop crawlers from indexing certain pages.
-fn crawl_web_page(url: impl IntoUrl) -> impl Iterator<Item = (Url, WebPageInfo)> {
-    gen_iter! {
-        let mut urls_visited = HashSet::new();
-        let mut urls_to_visit = VecDeque::new();
-        if let Ok(url) = url.into_url() {
-            urls_to_visit.push_back(url);
-        }
-
-        while let Some(url) = urls_to_visit.pop_front() {
-            urls_visited.insert(url.clone());
-            if let Ok(doc) = fetch_web_page(url.clone()) {
-                if let Ok(page) = get_web_page_info(doc) {
-                    for link_url in &page.links {
-                        // Ignore already-visited pages, so we don't get cycles.
-                        if !urls_visited.contains(link_url) {
-                            urls_to_visit.push_back(link_url.clone());
-                        }
-                    }
-                    yield (url.clone(), page);
-                }
+// Normalizes the percent-encoding of the path and query of the given URL, so that equivalent URLs compare equal:
+// escaped unreserved characters (e.g., `%7E` for `~`) are decoded, and other escapes use uppercase hex digits.
+// NOTE: this only applies to the URLs that pages are deduplicated by (and so fetched at); links are kept as found.
+fn normalize_percent_encoding(url: &mut Url) {
+    // Most URLs have no escapes at all, and needn't be rebuilt.
+    if url.path().contains('%') {
+        let path = normalize_escapes(url.path());
+        url.set_path(&path);
+    }
+    if let Some(query) = url.query().filter(|query| query.contains('%')).map(normalize_escapes) {
+        url.set_query(Some(&query));
+    }
+}
+
+fn normalize_escapes(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut normalized = String::with_capacity(s.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = match bytes.get(i..i + 3) {
+            Some(&[b'%', hi, lo]) if hi.is_ascii_hexdigit() && lo.is_ascii_hexdigit() => {
+                u8::from_str_radix(&s[i + 1..i + 3], 16).ok()
+            }
+            _ => None,
+        };
+        match escape {
+            Some(b) if b.is_ascii_alphanumeric() || b"-._~".contains(&b) => normalized.push(b as char),
+            Some(b) => normalized.push_str(&format!("%{:02X}", b)),
+            None => {
+                // URLs are always ASCII once parsed.
+                normalized.push(bytes[i] as char);
+                i += 1;
+                continue;
             }
         }
+        i += 3;
     }
+    normalized
 }
 
-// NOTE: ideally the test harness would spawn a temporary local HTTP server so as not to rely on the Web.
-#[cfg(test)]
-mod tests {
-    use is_match::is_match;
+// Sorts the parameters of the query of the given URL by name (and then value), so that URLs differing only in their
+// order compare equal.
+fn sort_query_params(url: &mut Url) {
+    let query = match url.query() {
+        Some(query) if query.contains('&') => query,
+        _ => return,
+    };
+    let mut params: Vec<_> = query.split('&').collect();
+    params.sort_by_key(|param| {
+        let mut name_value = param.splitn(2, '=');
+        (name_value.next().unwrap_or_default(), name_value.next())
+    });
+    let query = params.join("&");
+    url.set_query(Some(&query));
+}
 
-    use super::*;
+// Hashes the body of a page (or other resource), for `CrawlerBuilder::on_hashed`.
+// NOTE: as for `Soft404Config::hash_page`, the hash may differ between versions of Rust.
+fn content_hash(body: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    hasher.finish()
+}
 
-    #[test]
-    fn test_fetch_web_page() {
-        assert!(fetch_web_page("http://google.com/").is_ok());
-        assert!(fetch_web_page("http://bing.com/").is_ok());
-        assert!(fetch_web_page("https://en.wikipedia.org/wiki/Rust_(programming_language)").is_ok());
+// Parses the `Last-Modified` header, if any.
+fn header_last_modified(headers: &HeaderMap) -> Option<time::Timespec> {
+    let last_modified = headers.get(header::LAST_MODIFIED)?.to_str().ok()?;
+    Some(time::strptime(last_modified, "%a, %d %b %Y %H:%M:%S GMT").ok()?.to_timespec())
+}
 
-        assert!(is_match!(fetch_web_page("http://not.a.domain/"), Err(FetchWebPageError::HttpError(_))));
+// Parses the `article:modified_time` meta property (as used by the Open Graph protocol), if any.
+fn meta_last_modified(doc: &Document) -> Option<time::Timespec> {
+    doc.find(Name("meta"))
+        .filter(|n| n.attr("property") == Some("article:modified_time"))
+        .filter_map(|n| n.attr("content"))
+        .find_map(parse_iso8601)
+}
+
+// Parses an ISO 8601 date (e.g., `2015-10-21`) or date and time (e.g., `2015-10-21T07:28:00.123+02:00`), which is
+// taken to be in UTC unless it gives an offset.
+fn parse_iso8601(s: &str) -> Option<time::Timespec> {
+    let s = s.trim();
+    if s.len() == 10 {
+        return Some(time::strptime(s, "%Y-%m-%d").ok()?.to_timespec());
+    }
+    let date_time = time::strptime(s.get(..19)?, "%Y-%m-%dT%H:%M:%S").ok()?;
+    let mut rest = &s[19..];
+    // Ignore any fraction of a second.
+    if let Some(fraction) = rest.strip_prefix('.') {
+        rest = fraction.trim_start_matches(|c: char| c.is_ascii_digit());
+    }
+    let offset_seconds = match rest {
+        "" | "Z" | "z" => 0,
+        _ => {
+            let sign = match rest.as_bytes()[0] {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return None,
+            };
+            let offset = rest[1..].replace(':', "");
+            if offset.len() != 4 {
+                return None;
+            }
+            let hours: i64 = offset[..2].parse().ok()?;
+            let minutes: i64 = offset[2..].parse().ok()?;
+            sign * (hours * 60 + minutes) * 60
+        }
+    };
+    Some(date_time.to_timespec() - time::Duration::seconds(offset_seconds))
+}
 
-        assert!(is_match!(fetch_web_page("http://google.com/not_a_valid_url"), Err(FetchWebPageError::BadHttpStatus(StatusCode::NOT_FOUND))));
+// The subresources of the given page (images, scripts, stylesheets, frames and media) that would be fetched over plain
+// HTTP, in the order they appear (images first).
+fn find_mixed_content(url: &Url, doc: &Document, page: &WebPageInfo) -> Vec<Url> {
+    let base = document_base(url, doc);
+    let subresources = doc.find(
+        Name("script").or(Name("iframe")).or(Name("audio")).or(Name("video")).or(Name("embed")).or(Name("track"))
+    ).filter_map(|n| n.attr("src"));
+    let stylesheets = doc.find(Name("link")).filter(|n| {
+        n.attr("rel").is_some_and(|rel| rel.split_ascii_whitespace().any(|rel| rel.eq_ignore_ascii_case("stylesheet")))
+    }).filter_map(|n| n.attr("href"));
+    let other_urls: Vec<_> = subresources.chain(stylesheets).filter_map(|s| base.join(s).ok()).collect();
 
-        // TODO: test other sorts of errors here.
+    let mut seen = HashSet::new();
+    page.images.iter().cloned()
+        .chain(other_urls)
+        .filter(|url| url.scheme() == "http" && seen.insert(url.clone()))
+        .collect()
+}
+
+// Parses the URLs of the image candidates in a `srcset` attribute (e.g., `a.png 1x, b.png 2x`), dropping their
+// descriptors. URLs may themselves contain commas, so we follow the algorithm of the HTML standard rather than simply
+// splitting on commas.
+fn parse_srcset(srcset: &str) -> Vec<&str> {
+    let mut urls = vec![];
+    let mut rest = srcset;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_ascii_whitespace() || c == ',');
+        if rest.is_empty() {
+            return urls;
+        }
+
+        let url_end = rest.find(|c: char| c.is_ascii_whitespace()).unwrap_or(rest.len());
+        let (url, after_url) = rest.split_at(url_end);
+        // A URL ending in commas has no descriptors, and the commas just separate it from the next candidate.
+        let trimmed_url = url.trim_end_matches(',');
+        urls.push(trimmed_url);
+        if trimmed_url.len() < url.len() {
+            rest = after_url;
+            continue;
+        }
+
+        // Skip the descriptors, up to the next comma not within parentheses.
+        let mut in_parens = false;
+        let descriptors_end = after_url.char_indices().find(|&(_, c)| {
+            match c {
+                '(' => in_parens = true,
+                ')' => in_parens = false,
+                ',' if !in_parens => return true,
+                _ => {}
+            }
+            false
+        });
+        rest = match descriptors_end {
+            Some((i, _)) => &after_url[i + 1..],
+            None => "",
+        };
     }
+}
 
-    #[test]
-    fn test_web_page_info() {
-        let doc = fetch_web_page("http://rust-lang.org/").unwrap();
-        let doc_info = get_web_page_info(doc).unwrap();
-        assert_eq!(doc_info.title, "Rust Programming Language");
-        assert!(doc_info.links.contains(&"https://blog.rust-lang.org/".parse().unwrap()));
-        assert!(doc_info.links.contains(&"https://doc.rust-lang.org/".parse().unwrap()));
-        assert!(doc_info.links.contains(&"https://users.rust-lang.org/".parse().unwrap()));
+// Wraps a user-supplied callback, so that the types holding it can still be cloned and debug-printed.
+struct Callback<F: ?Sized>(Arc<F>);
 
-        // TODO: test info retrieved from other websites.
+impl<F: ?Sized> Clone for Callback<F> {
+    fn clone(&self) -> Self {
+        Callback(self.0.clone())
+    }
+}
 
-        // TODO: check for web page with no title.
+impl<F: ?Sized> fmt::Debug for Callback<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("<callback>")
     }
+}
 
-    #[test]
-    fn test_crawl_web_page() {
-        let pages = crawl_web_page("http://rust-lang.org/");
+impl<F: ?Sized> Deref for Callback<F> {
+    type Target = F;
 
-        let initial_pages: Vec<_> = pages.take(10).map(|(url, page)| (url.to_string(), page.title)).collect();
-        assert_eq!(&initial_pages[0],
-            &("http://rust-lang.org/".to_owned(), "Rust Programming Language".to_owned())
-        );
-        assert!(initial_pages.contains(
-            &("https://blog.rust-lang.org/".to_owned(), "The Rust Programming Language Blog".to_owned())
-        ));
-        assert!(initial_pages.contains(
-            &("https://github.com/rust-lang/rust/blob/master/CONTRIBUTING.md".to_owned(), "rust/CONTRIBUTING.md at master · rust-lang/rust · GitHub".to_owned())
-        ));
+    fn deref(&self) -> &F {
+        &self.0
+    }
+}
 
-        // TODO: test other websites, cyclic cases, pages with dead/invalid links, etc.
+const MAX_REDIRECTS: usize = 10;
+const DEFAULT_PROBE_TIMEOUT: Duration = Duration::from_secs(30);
+
+type FrontierFactory = dyn Fn() -> Box<dyn Frontier> + Send + Sync;
+type VisitedSetFactory = dyn Fn() -> Box<dyn VisitedSet> + Send + Sync;
+type UrlNormalizer = dyn Fn(&Url) -> Url + Send + Sync;
+type UrlRewriter = dyn Fn(&Url) -> Url + Send + Sync;
+type TokenProvider = dyn Fn() -> String + Send + Sync;
+type UrlPredicate = dyn Fn(&Url) -> bool + Send + Sync;
+type HashCallback = dyn Fn(&Url, u64) + Send + Sync;
+
+#[derive(Clone, Copy, Debug)]
+struct AdaptiveDelay {
+    initial: Duration,
+    max: Duration,
+}
+
+#[derive(Debug, Default)]
+struct HostDelay {
+    last_request: Option<Instant>,
+    penalty: Duration,
+}
+
+impl HostDelay {
+    fn wait(&mut self, request_delay: Duration) {
+        if let Some(last_request) = self.last_request {
+            let delay = request_delay + self.penalty;
+            let elapsed = last_request.elapsed();
+            if elapsed < delay {
+                thread::sleep(delay - elapsed);
+            }
+        }
+        self.last_request = Some(Instant::now());
+    }
+
+    fn adapt(&mut self, adaptive_delay: &AdaptiveDelay, overloaded: bool) {
+        self.penalty = if overloaded {
+            // Back off exponentially while the host is struggling...
+            (self.penalty * 2).max(adaptive_delay.initial).min(adaptive_delay.max)
+        } else if self.penalty / 2 >= adaptive_delay.initial {
+            // ...and recover gradually once it is fine again.
+            self.penalty / 2
+        } else {
+            Duration::from_secs(0)
+        };
+    }
+}
+
+// A token bucket, which lets through up to `rate` requests a second on average, with bursts of up to a second's worth
+// (after a lull), but at least one request.
+#[derive(Debug)]
+struct RateLimiter {
+    rate: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    fn new(rate: f64) -> RateLimiter {
+        RateLimiter {
+            rate,
+            tokens: rate.max(1.0),
+            last_refill: Instant::now(),
+        }
+    }
+
+    // Waits until a request may be made, and takes the token for it.
+    fn acquire(&mut self) {
+        self.refill();
+        if self.tokens < 1.0 {
+            thread::sleep(Duration::from_secs_f64((1.0 - self.tokens) / self.rate));
+            self.refill();
+        }
+        self.tokens -= 1.0;
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate.max(1.0));
+        self.last_refill = now;
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct CircuitBreaker {
+    max_failures: usize,
+    cooldown: Duration,
+}
+
+#[derive(Debug, Default)]
+struct HostCircuit {
+    consecutive_failures: usize,
+    opened_at: Option<Instant>,
+}
+
+impl HostCircuit {
+    fn is_open(&self, circuit_breaker: &CircuitBreaker) -> bool {
+        self.opened_at.is_some_and(|opened_at| opened_at.elapsed() < circuit_breaker.cooldown)
+    }
+
+    fn record(&mut self, circuit_breaker: &CircuitBreaker, failed: bool) {
+        if failed {
+            self.consecutive_failures += 1;
+            // Once open, a single failure (of the probe after the cooldown) is enough to open the circuit again.
+            if self.consecutive_failures >= circuit_breaker.max_failures {
+                self.opened_at = Some(Instant::now());
+            }
+        } else {
+            self.consecutive_failures = 0;
+            self.opened_at = None;
+        }
+    }
+}
+
+// The state of a crawl in progress, which is advanced by hand, so that crawling doesn't need generators (and thus
+// nightly Rust).
+struct CrawlIter {
+    crawler: Crawler,
+    seed: Url,
+    urls_visited: Box<dyn VisitedSet>,
+    urls_to_visit: Box<dyn Frontier>,
+    // The seed, if it was fetched before the crawl started.
+    fetched_seed: Option<(Arc<Url>, FetchedResource)>,
+    frontier_len: FrontierLen,
+    dropped_urls: Arc<AtomicUsize>,
+    host_delays: HashMap<Option<String>, HostDelay>,
+    rate_limiter: Option<RateLimiter>,
+    // The retries left for the rest of the crawl, if limited.
+    retry_budget: Option<usize>,
+    host_circuits: HashMap<Option<String>, HostCircuit>,
+    // The hosts crawled so far (keyed by `host_key`), each with the depth at which the crawl entered it.
+    hosts: HashMap<Option<String>, usize>,
+    // When watching for pagination loops, what's been seen of each paginated listing (keyed by its URL without the
+    // page parameter).
+    listings: HashMap<Url, Listing>,
+    // How many times the crawler had been cancelled when the crawl started.
+    cancels_at_start: usize,
+    page_count: usize,
+    host_page_counts: HashMap<String, usize>,
+    // The total size of the bodies fetched so far.
+    total_bytes: usize,
+    // How each queued URL was found.
+    queued: HashMap<Arc<Url>, Discovery>,
+    // The URLs that couldn't be fetched, if they're being recorded.
+    failures: Option<Vec<(Arc<Url>, FetchWebPageError)>>,
+    // When results are ordered, the number of URLs discovered so far, and the position of the URL being visited.
+    discovered_count: usize,
+    current_index: usize,
+    // When results are ordered, the outcome of each URL visited (`None` if it yielded no result) that can't be yielded
+    // until the URLs discovered before it have been visited too, and the position of the next URL to yield.
+    settled: BTreeMap<usize, Option<(CrawlResult, Option<Document>)>>,
+    next_index: usize,
+    finished: bool,
+}
+
+// What fetches the resources of a scheme.
+#[derive(Clone, Debug)]
+enum SchemeHandler {
+    // The crawler's own HTTP client.
+    Http,
+    Custom(Callback<dyn Fetcher>),
+}
+
+// How to spot endless pagination: a listing whose pages (given by the query parameter `param`) keep showing the same
+// content, `threshold` pages in a row.
+#[derive(Clone, Debug)]
+struct PaginationLoop {
+    param: String,
+    threshold: usize,
+}
+
+impl PaginationLoop {
+    // The listing that the given URL is a page of, if any, as the URL without the page parameter.
+    fn listing(&self, url: &Url) -> Option<Url> {
+        let query = url.query()?;
+        let is_page_param = |param: &&str| param.split('=').next() == Some(&*self.param);
+        if !query.split('&').any(|param| is_page_param(&param)) {
+            return None;
+        }
+        let other_params: Vec<_> = query.split('&').filter(|param| !is_page_param(param)).collect();
+        let other_params = other_params.join("&");
+        let mut listing = url.clone();
+        listing.set_query(if other_params.is_empty() { None } else { Some(&other_params) });
+        listing.set_fragment(None);
+        Some(listing)
+    }
+
+    // Hashes what the given page of the given listing shows, i.e., its links other than those to pages of the same
+    // listing (which, like page numbers, usually differ even once the content repeats).
+    fn content_hash(&self, listing: &Url, page: &WebPageInfo) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for link_url in &page.links {
+            let mut link_url = link_url.clone();
+            link_url.set_fragment(None);
+            if link_url != *listing && self.listing(&link_url).as_ref() != Some(listing) {
+                link_url.hash(&mut hasher);
+            }
+        }
+        hasher.finish()
+    }
+}
+
+// What's been seen of a paginated listing.
+#[derive(Clone, Copy, Debug)]
+struct Listing {
+    // The content hash of the last page visited, and how many pages in a row have had it.
+    last_hash: u64,
+    repeats: usize,
+}
+
+// How a queued URL was found.
+#[derive(Clone, Copy, Debug, Default)]
+struct Discovery {
+    // How many links from the seed it was found by.
+    depth: usize,
+    // The position in which it was discovered, for ordering results.
+    index: usize,
+}
+
+impl Iterator for CrawlIter {
+    type Item = (CrawlResult, Option<Document>);
+
+    fn next(&mut self) -> Option<(CrawlResult, Option<Document>)> {
+        if !self.crawler.ordered {
+            return self.next_fetched();
+        }
+        loop {
+            while let Some(outcome) = self.settled.remove(&self.next_index) {
+                self.next_index += 1;
+                if outcome.is_some() {
+                    return outcome;
+                }
+            }
+            if self.finished {
+                // Whatever is left in the frontier will never be visited, so skip over it.
+                let (&index, _) = self.settled.iter().next()?;
+                self.next_index = index;
+                continue;
+            }
+            if let Some(result) = self.next_fetched() {
+                self.settled.insert(self.current_index, Some(result));
+            }
+        }
+    }
+}
+
+impl CrawlIter {
+    fn next_fetched(&mut self) -> Option<(CrawlResult, Option<Document>)> {
+        if self.finished {
+            return None;
+        }
+        let CrawlIter {
+            crawler,
+            seed,
+            urls_visited,
+            urls_to_visit,
+            fetched_seed,
+            frontier_len,
+            dropped_urls,
+            host_delays,
+            rate_limiter,
+            retry_budget,
+            host_circuits,
+            hosts,
+            listings,
+            cancels_at_start,
+            page_count,
+            host_page_counts,
+            total_bytes,
+            queued,
+            failures,
+            discovered_count,
+            current_index,
+            settled,
+            next_index: _,
+            finished,
+        } = self;
+
+        while let Some(url) = urls_to_visit.pop() {
+            // The seed was found by no links at all.
+            let Discovery { depth, index } = queued.remove(&url).unwrap_or_default();
+            if crawler.ordered {
+                *current_index = index;
+                settled.insert(*current_index, None);
+            }
+            if crawler.cancel_handle.cancels() != *cancels_at_start {
+                info!("cancelled crawl at {}", seed);
+                break;
+            }
+            if crawler.max_pages.is_some_and(|max_pages| *page_count >= max_pages) {
+                info!("reached maximum of {} pages", page_count);
+                break;
+            }
+            if crawler.max_total_bytes.is_some_and(|max_bytes| *total_bytes >= max_bytes) {
+                info!("reached maximum of {} bytes", total_bytes);
+                break;
+            }
+
+            let host_page_count = host_page_counts.entry(url.host_str().unwrap_or_default().to_owned()).or_insert(0);
+            if crawler.max_pages_per_host.is_some_and(|max_pages| *host_page_count >= max_pages) {
+                debug!("skipping {}: reached maximum of {} pages for host", url, host_page_count);
+                continue;
+            }
+
+            let host_circuit = host_circuits.entry(url.host_str().map(str::to_owned)).or_insert_with(HostCircuit::default);
+            if crawler.circuit_breaker.is_some_and(|circuit_breaker| host_circuit.is_open(&circuit_breaker)) {
+                debug!("skipping {}: host is failing", url);
+                continue;
+            }
+
+            if crawler.respect_robots_txt && !crawler.robots(&url).is_allowed(&url) {
+                debug!("skipping {}: disallowed by robots.txt", url);
+                continue;
+            }
+
+            if crawler.should_process.as_ref().is_some_and(|should_process| !should_process(&url)) {
+                debug!("skipping {}: not to be processed", url);
+                continue;
+            }
+
+            let host_delay = host_delays.entry(url.host_str().map(str::to_owned)).or_insert_with(HostDelay::default);
+            host_delay.wait(crawler.request_delay);
+            // We may have been cancelled while waiting.
+            if crawler.cancel_handle.cancels() != *cancels_at_start {
+                info!("cancelled crawl at {}", seed);
+                break;
+            }
+            let mut fetched = match fetched_seed.take() {
+                Some(fetched_seed) => Ok(fetched_seed),
+                None => crawler.fetch_following_redirects(&url, &mut **urls_visited, rate_limiter),
+            };
+            let mut retries = 0;
+            while retries < crawler.max_retries && fetched.as_ref().err().is_some_and(FetchWebPageError::is_overload) {
+                if *retry_budget == Some(0) {
+                    debug!("not retrying {}: no retries left for the crawl", url);
+                    break;
+                }
+                if let Some(retry_budget) = retry_budget {
+                    *retry_budget -= 1;
+                }
+                retries += 1;
+                if let Some(adaptive_delay) = &crawler.adaptive_delay {
+                    host_delay.adapt(adaptive_delay, true);
+                }
+                host_delay.wait(crawler.request_delay);
+                debug!("retrying {} (attempt {} of {})", url, retries + 1, crawler.max_retries + 1);
+                fetched = crawler.fetch_following_redirects(&url, &mut **urls_visited, rate_limiter);
+            }
+            if let Some(adaptive_delay) = &crawler.adaptive_delay {
+                host_delay.adapt(adaptive_delay, fetched.as_ref().err().is_some_and(FetchWebPageError::is_overload));
+            }
+            if let Some(circuit_breaker) = &crawler.circuit_breaker {
+                host_circuit.record(circuit_breaker, fetched.as_ref().err().is_some_and(FetchWebPageError::is_host_failure));
+            }
+
+            // From here on, we're interested in the page we actually landed on.
+            let (url, fetched) = match fetched {
+                Ok(fetched) => fetched,
+                Err(err) => {
+                    warn!("skipping {}: {}", url, err);
+                    if let Some(failures) = failures {
+                        failures.push((url, err));
+                    }
+                    continue;
+                }
+            };
+
+            *total_bytes += fetched.byte_size;
+            let secure = url.scheme() == "https";
+
+            // The server may tell us where else the same content lives, in which case only crawl it once.
+            let content_location = fetched.content_location.as_ref().and_then(|location| url.join(location).ok());
+            if let Some(mut content_location) = content_location {
+                content_location.set_fragment(None);
+                if content_location != *url {
+                    if urls_visited.contains(&content_location) {
+                        debug!("skipping {}: same content as {}", url, content_location);
+                        continue;
+                    }
+                    urls_visited.insert(&Arc::new(content_location));
+                }
+            }
+
+            if let Some(mirror_dir) = &crawler.mirror_to {
+                let body = match &fetched.html {
+                    Some(html) => html.body.as_bytes(),
+                    None => fetched.body.as_deref().unwrap_or_default(),
+                };
+                if let Err(err) = mirror::save(mirror_dir, &url, body) {
+                    warn!("failed to mirror {}: {}", url, err);
+                }
+            }
+
+            if let Some(on_hashed) = &crawler.on_hashed {
+                let body = match &fetched.html {
+                    Some(html) => Some(html.body.as_bytes()),
+                    None => fetched.body.as_deref(),
+                };
+                if let Some(body) = body {
+                    on_hashed(&url, content_hash(body));
+                }
+            }
+
+            let timing = fetched.timing();
+            let html = match fetched.html {
+                Some(html) => html,
+                None => {
+                    *page_count += 1;
+                    *host_page_count += 1;
+                    frontier_len.set(urls_to_visit.len());
+                    return Some((CrawlResult {
+                        url,
+                        status: fetched.status,
+                        depth,
+                        page: None,
+                        content_type: fetched.content_type,
+                        byte_size: fetched.byte_size,
+                        http_version: fetched.http_version,
+                        timing,
+                        redirect_chain: fetched.redirect_chain,
+                        last_modified: header_last_modified(&fetched.headers),
+                        secure,
+                        mixed_content: vec![],
+                        headers: fetched.headers,
+                        raw_html: None,
+                        soft_404: false,
+                        links_total: 0,
+                        links_followed: 0,
+                    }, None));
+                }
+            };
+            let soft_404_config = crawler.soft_404.as_ref();
+            let info = match crawler.parse_timeout {
+                // Without a document, there's nothing more to go on than the title and links.
+                _ if crawler.link_extraction == LinkExtraction::Streaming => {
+                    streaming::get_web_page_info(&url, &html.body, &crawler.link_attributes).map(|page| DocumentInfo {
+                        page,
+                        mixed_content: vec![],
+                        soft_404: false,
+                        last_modified: None,
+                    })
+                }
+                Some(parse_timeout) => {
+                    document_info_within(&url, &html.body, &crawler.link_attributes, soft_404_config, parse_timeout)
+                }
+                None => document_info(&url, &html.body, &html.document, &crawler.link_attributes, soft_404_config),
+            };
+            let DocumentInfo { mut page, mixed_content, soft_404, last_modified } = match info {
+                Ok(info) => info,
+                Err(err) => {
+                    warn!("skipping {}: {}", url, err);
+                    continue;
+                }
+            };
+
+            if !crawler.keep_fragment_links {
+                page.links.retain(|link_url| !is_same_page_fragment(&url, link_url));
+                page.link_details.retain(|link| !is_same_page_fragment(&url, &link.url));
+            }
+            // Links are internal relative to the seed rather than the page itself, but needn't be cloned again for it.
+            let seed_host = host_key(seed, crawler.treat_www_as_same);
+            for link in &mut page.link_details {
+                link.internal = host_key(&link.url, crawler.treat_www_as_same) == seed_host;
+            }
+
+            if soft_404 {
+                debug!("not following links of {}: looks like an error page", url);
+            }
+
+            if let Some(pagination_loop) = &crawler.pagination_loop {
+                if let Some(listing_url) = pagination_loop.listing(&url) {
+                    let hash = pagination_loop.content_hash(&listing_url, &page);
+                    let listing = listings.entry(listing_url).or_insert(Listing {
+                        last_hash: hash,
+                        repeats: 0,
+                    });
+                    listing.repeats = if listing.last_hash == hash { listing.repeats + 1 } else { 1 };
+                    listing.last_hash = hash;
+                }
+            }
+
+            let unacceptable_links: HashSet<_> = page.link_types.iter()
+                .filter(|(_, content_type)| crawler.respect_link_types && !crawler.accepts_content_type(content_type))
+                .map(|(link_url, _)| link_url)
+                .collect();
+            let links_total = page.links.len();
+            let mut links_followed = 0;
+            for link_url in page.links.iter().filter(|_| !soft_404) {
+                if unacceptable_links.contains(link_url) {
+                    debug!("not following {}: declared as non-HTML", link_url);
+                    continue;
+                }
+
+                // Fragments never identify a distinct page, so don't fetch the same page once for each.
+                let mut link_url = link_url.clone();
+                link_url.set_fragment(None);
+                normalize_percent_encoding(&mut link_url);
+                crawler.normalize_link(&mut link_url);
+                if crawler.treat_www_as_same {
+                    move_to_seed_host(seed, &mut link_url);
+                }
+
+                // Ignore already-visited (or already-queued) pages, so we don't get cycles.
+                if urls_visited.contains(&link_url) || !crawler.should_follow(seed, &link_url) {
+                    continue;
+                }
+
+                if let Some(pagination_loop) = &crawler.pagination_loop {
+                    let listing = pagination_loop.listing(&link_url).and_then(|listing_url| listings.get(&listing_url));
+                    if listing.is_some_and(|listing| listing.repeats >= pagination_loop.threshold) {
+                        debug!("not following {}: pagination seems to go on forever", link_url);
+                        continue;
+                    }
+                }
+
+                // Depth within a host counts the links since the crawl first entered it, so that leaving the host and
+                // coming back doesn't start it over.
+                let host = host_key(&link_url, crawler.treat_www_as_same).map(str::to_owned);
+                let host_entry_depth = hosts.get(&host).cloned();
+                let link_host_depth = host_entry_depth.map_or(0, |entry_depth| (depth + 1).saturating_sub(entry_depth));
+                if let Some(max_depth) = crawler.max_depth_for(&link_url) {
+                    if link_host_depth > max_depth {
+                        debug!("not following {}: deeper than {} links into host", link_url, max_depth);
+                        continue;
+                    }
+                }
+
+                let new_host = host_entry_depth.is_none();
+                if new_host && crawler.max_hosts.is_some_and(|max_hosts| hosts.len() >= max_hosts) {
+                    continue;
+                }
+
+                // A dropped link doesn't take up one of the hosts allowed, in case it's never found again.
+                if crawler.max_frontier.is_some_and(|max_frontier| urls_to_visit.len() >= max_frontier) {
+                    debug!("dropping {}: frontier is full", link_url);
+                    dropped_urls.fetch_add(1, Ordering::SeqCst);
+                    continue;
+                }
+                if new_host {
+                    hosts.insert(host, depth + 1);
+                }
+
+                // From here on, the URL is shared rather than cloned.
+                let link_url = Arc::new(link_url);
+                urls_visited.insert(&link_url);
+                queued.insert(Arc::clone(&link_url), Discovery {
+                    depth: depth + 1,
+                    index: *discovered_count,
+                });
+                *discovered_count += 1;
+                urls_to_visit.push(link_url);
+                links_followed += 1;
+            }
+
+            *page_count += 1;
+            *host_page_count += 1;
+            frontier_len.set(urls_to_visit.len());
+            return Some((CrawlResult {
+                url,
+                status: fetched.status,
+                depth,
+                page: Some(page),
+                content_type: fetched.content_type,
+                byte_size: fetched.byte_size,
+                http_version: fetched.http_version,
+                timing,
+                redirect_chain: fetched.redirect_chain,
+                last_modified: header_last_modified(&fetched.headers).or(last_modified),
+                secure,
+                mixed_content,
+                headers: fetched.headers,
+                raw_html: if crawler.keep_raw_html { Some(html.body) } else { None },
+                soft_404,
+                links_total,
+                links_followed,
+            }, if crawler.skips_documents() { None } else { Some(html.document) }));
+        }
+
+        frontier_len.set(urls_to_visit.len());
+        info!("finished crawl at {} after {} pages", seed, page_count);
+        *finished = true;
+        None
+    }
+}
+
+// Crawlers are `Send + Sync`, so that one may be shared between threads (e.g., in an `Arc`).
+#[derive(Clone, Debug)]
+pub struct Crawler {
+    client: Client,
+    path_prefixes: Vec<String>,
+    keep_fragment_links: bool,
+    link_attributes: Vec<String>,
+    treat_www_as_same: bool,
+    sort_query_params: bool,
+    trailing_slash: TrailingSlash,
+    directory_index: Option<String>,
+    respect_robots_txt: bool,
+    respect_link_types: bool,
+    resolve: Vec<(String, SocketAddr)>,
+    host_headers: Vec<(String, String)>,
+    request_headers: HeaderMap,
+    response_cache: Option<ResponseCache>,
+    keep_raw_html: bool,
+    fail_on_seed_error: bool,
+    timing: bool,
+    mirror_to: Option<PathBuf>,
+    max_hosts: Option<usize>,
+    request_delay: Duration,
+    rate_limit: Option<f64>,
+    max_retries: usize,
+    max_total_retries: Option<usize>,
+    max_frontier: Option<usize>,
+    connect_timeout: Option<Duration>,
+    parse_timeout: Option<Duration>,
+    link_extraction: LinkExtraction,
+    adaptive_delay: Option<AdaptiveDelay>,
+    circuit_breaker: Option<CircuitBreaker>,
+    extra_content_types: Vec<String>,
+    accepted_statuses: Option<Vec<StatusCode>>,
+    allowed_ports: Option<Vec<u16>>,
+    max_pages: Option<usize>,
+    max_pages_per_host: Option<usize>,
+    max_total_bytes: Option<usize>,
+    max_depth: Option<usize>,
+    max_depth_per_host: HashMap<String, usize>,
+    max_body_size: Option<usize>,
+    max_path_depth: Option<usize>,
+    max_path_segment_repeats: Option<usize>,
+    pagination_loop: Option<PaginationLoop>,
+    frontier_capacity: usize,
+    visited_capacity: usize,
+    frontier: Option<Callback<FrontierFactory>>,
+    visited_set: Option<Callback<VisitedSetFactory>>,
+    preseeded_urls: Vec<Url>,
+    url_normalizer: Option<Callback<UrlNormalizer>>,
+    fetch_url_rewriter: Option<Callback<UrlRewriter>>,
+    token_provider: Option<Callback<TokenProvider>>,
+    fetchers: HashMap<String, SchemeHandler>,
+    should_process: Option<Callback<UrlPredicate>>,
+    on_hashed: Option<Callback<HashCallback>>,
+    login: Option<LoginConfig>,
+    soft_404: Option<Soft404Config>,
+    ordered: bool,
+    cancel_handle: CancelHandle,
+    // The robots rules of each origin, shared between clones so that priming one primes them all.
+    robots_cache: Arc<Mutex<HashMap<String, Arc<Robots>>>>,
+    www_fallback: bool,
+    // The hosts that failed to resolve, and the `www.` variant of each that was fetched instead, shared between clones.
+    www_fallbacks: Arc<Mutex<HashMap<String, String>>>,
+}
+
+#[derive(Debug, Default)]
+pub struct CrawlerBuilder {
+    client: Option<Client>,
+    path_prefixes: Vec<String>,
+    keep_fragment_links: bool,
+    link_attributes: Option<Vec<String>>,
+    treat_www_as_same: bool,
+    sort_query_params: bool,
+    trailing_slash: TrailingSlash,
+    directory_index: Option<String>,
+    respect_robots_txt: bool,
+    respect_link_types: bool,
+    resolve: Vec<(String, SocketAddr)>,
+    host_headers: Vec<(String, String)>,
+    request_headers: HeaderMap,
+    response_cache: Option<ResponseCache>,
+    keep_raw_html: bool,
+    fail_on_seed_error: bool,
+    timing: bool,
+    mirror_to: Option<PathBuf>,
+    max_hosts: Option<usize>,
+    request_delay: Duration,
+    rate_limit: Option<f64>,
+    max_retries: usize,
+    max_total_retries: Option<usize>,
+    max_frontier: Option<usize>,
+    connect_timeout: Option<Duration>,
+    timeout: Option<Duration>,
+    parse_timeout: Option<Duration>,
+    link_extraction: LinkExtraction,
+    adaptive_delay: Option<AdaptiveDelay>,
+    circuit_breaker: Option<CircuitBreaker>,
+    extra_content_types: Vec<String>,
+    accepted_statuses: Option<Vec<StatusCode>>,
+    allowed_ports: Option<Vec<u16>>,
+    max_pages: Option<usize>,
+    max_pages_per_host: Option<usize>,
+    max_total_bytes: Option<usize>,
+    max_depth: Option<usize>,
+    max_depth_per_host: HashMap<String, usize>,
+    max_body_size: Option<usize>,
+    max_path_depth: Option<usize>,
+    max_path_segment_repeats: Option<usize>,
+    pagination_loop: Option<PaginationLoop>,
+    frontier_capacity: usize,
+    visited_capacity: usize,
+    frontier: Option<Callback<FrontierFactory>>,
+    visited_set: Option<Callback<VisitedSetFactory>>,
+    preseeded_urls: Vec<Url>,
+    www_fallback: bool,
+    url_normalizer: Option<Callback<UrlNormalizer>>,
+    fetch_url_rewriter: Option<Callback<UrlRewriter>>,
+    token_provider: Option<Callback<TokenProvider>>,
+    fetchers: HashMap<String, SchemeHandler>,
+    should_process: Option<Callback<UrlPredicate>>,
+    on_hashed: Option<Callback<HashCallback>>,
+    login: Option<LoginConfig>,
+    soft_404: Option<Soft404Config>,
+    interleave_hosts: bool,
+    deterministic: bool,
+    ordered: bool,
+}
+
+impl Crawler {
+    pub fn new() -> Crawler {
+        Crawler::builder().build().expect("failed to build crawler")
+    }
+
+    pub fn builder() -> CrawlerBuilder {
+        CrawlerBuilder::default()
+    }
+
+    // NOTE: ideally we'd make this a stream of futures (`FuturesUnordered`) and leverage parallelism, but this would take a lot more effort and care.
+    pub fn crawl(&self, url: impl IntoUrl) -> Result<Crawl<impl Iterator<Item = CrawlResult>>, CrawlError> {
+        self.clone().into_iter(url)
+    }
+
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.cancel_handle.clone()
+    }
+
+    // Fetches the `robots.txt` of the hosts of the given URLs ahead of crawling, returning the sitemaps they advertise.
+    pub fn prime(&self, urls: impl IntoIterator<Item = Url>) -> Vec<Url> {
+        urls.into_iter().flat_map(|url| self.robots(&url).sitemaps.clone()).collect()
+    }
+
+    // Like `crawl`, but only yields the pages matching the given predicate (so never resources other than HTML pages).
+    // The links of every page are still followed.
+    pub fn crawl_filtered(
+        &self,
+        url: impl IntoUrl,
+        predicate: impl Fn(&WebPageInfo) -> bool,
+    ) -> Result<Crawl<impl Iterator<Item = CrawlResult>>, CrawlError> {
+        let crawl = self.crawl(url)?;
+        Ok(Crawl {
+            results: crawl.results.filter(move |result| result.page.as_ref().is_some_and(&predicate)),
+            frontier_len: crawl.frontier_len,
+            dropped_urls: crawl.dropped_urls,
+        })
+    }
+
+    // Crawls the whole site, returning the results ordered so that pages come before the pages they link to.
+    pub fn crawl_sorted(&self, url: impl IntoUrl) -> Result<Vec<CrawlResult>, CrawlError> {
+        Ok(sort_topologically(self.crawl(url)?.collect()))
+    }
+
+    // Crawls on a background thread, sending the results through a channel that holds at most `capacity` of them. When
+    // the channel is full, the crawl pauses until the receiver catches up, so a slow consumer doesn't cause results to
+    // pile up in memory. The crawl stops once the receiver is dropped.
+    pub fn crawl_in_background(&self, url: impl IntoUrl, capacity: usize) -> Result<Receiver<CrawlResult>, CrawlError> {
+        let seed = url.into_url().map_err(CrawlError::InvalidSeed)?;
+        let crawler = self.clone();
+        let (started_sender, started_receiver) = mpsc::channel();
+        let (result_sender, result_receiver) = mpsc::sync_channel(capacity);
+        thread::spawn(move || {
+            let crawl = match crawler.into_iter(seed) {
+                Ok(crawl) => {
+                    let _ = started_sender.send(Ok(()));
+                    crawl
+                }
+                Err(err) => {
+                    let _ = started_sender.send(Err(err));
+                    return;
+                }
+            };
+            for result in crawl {
+                if result_sender.send(result).is_err() {
+                    break;
+                }
+            }
+        });
+        started_receiver.recv().expect("crawl thread panicked")?;
+        Ok(result_receiver)
+    }
+
+    // Crawls from the given URL, yielding each link found on any page once.
+    pub fn crawl_links(&self, url: impl IntoUrl) -> Result<impl Iterator<Item = Url>, CrawlError> {
+        let mut seen = HashSet::new();
+        Ok(self.crawl(url)?
+            .filter_map(|result| result.page)
+            .flat_map(|page| page.links)
+            .filter(move |link_url| {
+                let mut key = link_url.clone();
+                normalize_percent_encoding(&mut key);
+                seen.insert(key)
+            }))
+    }
+
+    // Crawls from the given URL, returning the external links (i.e., those to other hosts than the seed's) found on
+    // internal pages, along with the number of internal pages linking to each. Use `max_hosts(1)` to avoid crawling
+    // the external pages themselves.
+    pub fn crawl_external_links(&self, url: impl IntoUrl) -> Result<HashMap<Url, usize>, CrawlError> {
+        let seed = url.into_url().map_err(CrawlError::InvalidSeed)?;
+        let mut external_links = HashMap::new();
+        for result in self.crawl(seed.clone())? {
+            if result.url.host_str() != seed.host_str() {
+                continue;
+            }
+            let page = match result.page {
+                Some(page) => page,
+                None => continue,
+            };
+            let link_urls: HashSet<_> = page.links.into_iter()
+                .filter(|link_url| link_url.host_str() != seed.host_str())
+                .map(|mut link_url| {
+                    link_url.set_fragment(None);
+                    normalize_percent_encoding(&mut link_url);
+                    link_url
+                })
+                .collect();
+            for link_url in link_urls {
+                *external_links.entry(link_url).or_insert(0) += 1;
+            }
+        }
+        Ok(external_links)
+    }
+
+    // Crawls the site at the given URL, reporting every internal link that leads to an error status (e.g., 404), along
+    // with the pages that link to it.
+    pub fn audit(&self, url: impl IntoUrl) -> Result<LinkReport, CrawlError> {
+        let mut crawl_iter = self.clone().start_crawl(url)?;
+        crawl_iter.failures = Some(vec![]);
+        let results: Vec<_> = crawl_iter.by_ref().map(|(result, _)| result).collect();
+
+        let seed = &crawl_iter.seed;
+        let broken_links = crawl_iter.failures.take().into_iter().flatten().filter_map(|(url, err)| {
+            let status = match err {
+                FetchWebPageError::BadHttpStatus(status) if url.host_str() == seed.host_str() => status,
+                _ => return None,
+            };
+            let referrers = results.iter()
+                .filter(|result| result.url.host_str() == seed.host_str())
+                .filter(|result| result.page.iter().flat_map(|page| &page.links).any(|link_url| {
+                    let mut link_url = link_url.clone();
+                    link_url.set_fragment(None);
+                    normalize_percent_encoding(&mut link_url);
+                    link_url == *url
+                }))
+                .map(|result| Url::clone(&result.url))
+                .collect();
+            Some(BrokenLink {
+                url: unshare_url(url),
+                status,
+                referrers,
+            })
+        }).collect();
+        Ok(LinkReport {
+            broken_links,
+        })
+    }
+
+    // Fetches the page at the given URL and checks each of its links with a `HEAD` request (or a `GET` for servers that
+    // don't allow `HEAD`), without crawling any further. The request delay and rate limit apply as when crawling.
+    // NOTE: links of a scheme with its own fetcher are checked with that fetcher instead. A client injected with
+    // `client(...)` that follows redirects makes each link report the status it finally leads to, not its own.
+    pub fn check_page(&self, url: impl IntoUrl) -> Result<PageCheck, CrawlError> {
+        let mut url = self.seed_url(url)?;
+        normalize_percent_encoding(&mut url);
+        let mut rate_limiter = self.rate_limit.map(RateLimiter::new);
+        let mut host_delays = HashMap::new();
+        host_delays.entry(url.host_str().map(str::to_owned)).or_insert_with(HostDelay::default).wait(self.request_delay);
+        let (url, fetched) = self.fetch_following_redirects(&Arc::new(url), &mut HashVisitedSet::default(), &mut rate_limiter)
+            .map_err(CrawlError::SeedFailed)?;
+        let mut wait_for = |url: &Url| {
+            host_delays.entry(url.host_str().map(str::to_owned)).or_insert_with(HostDelay::default).wait(self.request_delay);
+            if let Some(rate_limiter) = &mut rate_limiter {
+                rate_limiter.acquire();
+            }
+        };
+        let link_urls = match &fetched.html {
+            Some(html) => {
+                let parsed;
+                let document = if self.skips_documents() {
+                    parsed = Document::from(&*html.body);
+                    &parsed
+                } else {
+                    &html.document
+                };
+                let base = document_base(&url, document);
+                distinct_links(&link_nodes(&base, document, &self.link_attributes))
+            }
+            None => vec![],
+        };
+
+        let mut links_seen = HashSet::new();
+        let links = link_urls.into_iter()
+            .filter(|link_url| self.fetchers.contains_key(link_url.scheme()))
+            .map(|mut link_url| {
+                link_url.set_fragment(None);
+                normalize_percent_encoding(&mut link_url);
+                link_url
+            })
+            .filter(|link_url| links_seen.insert(link_url.clone()))
+            .map(|link_url| {
+                wait_for(&link_url);
+                let fetch_url = self.fetch_url(&link_url);
+                let status = if let Some(SchemeHandler::Custom(fetcher)) = self.fetchers.get(fetch_url.scheme()) {
+                    // As when crawling, what the fetcher gives is taken as a successful response.
+                    match fetcher.fetch(&fetch_url) {
+                        Ok(_) => Some(StatusCode::OK),
+                        Err(FetchWebPageError::BadHttpStatus(status)) => Some(status),
+                        Err(_) => None,
+                    }
+                } else {
+                    let mut status = self.request(Method::HEAD, &fetch_url).send().ok().map(|resp| resp.status());
+                    if status == Some(StatusCode::METHOD_NOT_ALLOWED) {
+                        wait_for(&link_url);
+                        status = self.request(Method::GET, &fetch_url).send().ok().map(|resp| resp.status());
+                    }
+                    status
+                };
+                debug!("checked {}: {:?}", link_url, status);
+                LinkCheck {
+                    url: link_url,
+                    status,
+                }
+            })
+            .collect();
+        Ok(PageCheck {
+            url: unshare_url(url),
+            links,
+        })
+    }
+
+    // Like `crawl`, but also yields an error for each page that couldn't be fetched, before the next result.
+    pub fn crawl_attempts(&self, url: impl IntoUrl) -> Result<Crawl<impl Iterator<Item = CrawlAttempt>>, CrawlError> {
+        let mut crawl_iter = self.clone().start_crawl(url)?;
+        crawl_iter.failures = Some(vec![]);
+        let frontier_len = crawl_iter.frontier_len.clone();
+        let dropped_urls = crawl_iter.dropped_urls.clone();
+        let mut attempts = VecDeque::new();
+        let results = iter::from_fn(move || {
+            if attempts.is_empty() {
+                let result = crawl_iter.next();
+                attempts.extend(crawl_iter.failures.iter_mut().flat_map(|failures| failures.drain(..))
+                    .map(|(url, error)| Err(Box::new(CrawlFailure { url, error }))));
+                attempts.extend(result.map(|(result, _)| Ok(result)));
+            }
+            attempts.pop_front()
+        });
+        Ok(Crawl {
+            results,
+            frontier_len,
+            dropped_urls,
+        })
+    }
+
+    // Like `crawl`, but consumes the crawler.
+    pub fn into_iter(self, url: impl IntoUrl) -> Result<Crawl<impl Iterator<Item = CrawlResult>>, CrawlError> {
+        let crawl = self.into_iter_with_documents(url)?;
+        Ok(Crawl {
+            results: crawl.results.map(|(result, _)| result),
+            frontier_len: crawl.frontier_len,
+            dropped_urls: crawl.dropped_urls,
+        })
+    }
+
+    // Like `crawl`, but also yields the parsed document of each HTML page, for running further queries on it.
+    // NOTE: documents aren't part of `CrawlResult` itself, since they're big, and can't be sent between threads.
+    pub fn crawl_with_documents(&self, url: impl IntoUrl) -> Result<Crawl<impl Iterator<Item = (CrawlResult, Option<Document>)>>, CrawlError> {
+        self.clone().into_iter_with_documents(url)
+    }
+
+    // Parses the URL a crawl (or check) starts from.
+    fn seed_url(&self, url: impl IntoUrl) -> Result<Url, CrawlError> {
+        match url.into_url() {
+            Ok(seed) => Ok(seed),
+            // The client only takes HTTP(S) URLs, but those of other schemes are fine if there's a fetcher for them.
+            Err(err) => match err.url() {
+                Some(seed) if matches!(self.fetchers.get(seed.scheme()), Some(SchemeHandler::Custom(_))) => Ok(seed.clone()),
+                _ => Err(CrawlError::InvalidSeed(err)),
+            },
+        }
+    }
+
+    fn start_crawl(self, url: impl IntoUrl) -> Result<CrawlIter, CrawlError> {
+        // NOTE: parsing already lowercases the scheme and host, so URLs can be compared (and their hosts used as keys)
+        // as they are; only the path and query need normalizing.
+        let mut seed = self.seed_url(url)?;
+        normalize_percent_encoding(&mut seed);
+        self.normalize_link(&mut seed);
+        if let Some(login) = &self.login {
+            self.log_in(login).map_err(CrawlError::LoginFailed)?;
+        }
+        let crawler = self;
+        let cancels_at_start = crawler.cancel_handle.cancels();
+        let mut urls_visited: Box<dyn VisitedSet> = match &crawler.visited_set {
+            Some(visited_set) => visited_set(),
+            None => Box::new(crawler.default_visited_set()),
+        };
+        let mut urls_to_visit: Box<dyn Frontier> = match &crawler.frontier {
+            Some(frontier) => frontier(),
+            None => Box::new(crawler.default_frontier()),
+        };
+        for url in &crawler.preseeded_urls {
+            let mut url = url.clone();
+            url.set_fragment(None);
+            normalize_percent_encoding(&mut url);
+            crawler.normalize_link(&mut url);
+            urls_visited.insert(&Arc::new(url));
+        }
+        info!("starting crawl at {}", seed);
+        let seed_url = Arc::new(seed.clone());
+        urls_visited.insert(&seed_url);
+        urls_to_visit.push(Arc::clone(&seed_url));
+
+        // Fetch the seed right away if failing to do so should fail the crawl, rather than just yield no results.
+        let mut rate_limiter = crawler.rate_limit.map(RateLimiter::new);
+        let fetched_seed = if crawler.fail_on_seed_error {
+            let fetched_seed = crawler.fetch_following_redirects(&seed_url, &mut *urls_visited, &mut rate_limiter);
+            Some(fetched_seed.map_err(CrawlError::SeedFailed)?)
+        } else {
+            None
+        };
+
+        let frontier_len = FrontierLen::default();
+        frontier_len.set(urls_to_visit.len());
+        let mut hosts = HashMap::new();
+        hosts.insert(host_key(&seed, crawler.treat_www_as_same).map(str::to_owned), 0);
+        let retry_budget = crawler.max_total_retries;
+        Ok(CrawlIter {
+            crawler,
+            seed,
+            urls_visited,
+            urls_to_visit,
+            fetched_seed,
+            frontier_len,
+            dropped_urls: Arc::new(AtomicUsize::new(0)),
+            host_delays: HashMap::new(),
+            rate_limiter,
+            retry_budget,
+            host_circuits: HashMap::new(),
+            hosts,
+            listings: HashMap::new(),
+            cancels_at_start,
+            page_count: 0,
+            host_page_counts: HashMap::new(),
+            total_bytes: 0,
+            queued: HashMap::new(),
+            failures: None,
+            discovered_count: 1,
+            current_index: 0,
+            settled: BTreeMap::new(),
+            next_index: 0,
+            finished: false,
+        })
+    }
+
+    fn into_iter_with_documents(self, url: impl IntoUrl) -> Result<Crawl<impl Iterator<Item = (CrawlResult, Option<Document>)>>, CrawlError> {
+        let crawl_iter = self.start_crawl(url)?;
+        let frontier_len = crawl_iter.frontier_len.clone();
+        let dropped_urls = crawl_iter.dropped_urls.clone();
+        #[cfg(feature = "nightly")]
+        let results = gen_iter! {
+            for result in crawl_iter {
+                yield result;
+            }
+        };
+        #[cfg(not(feature = "nightly"))]
+        let results = crawl_iter;
+        Ok(Crawl {
+            results,
+            frontier_len,
+            dropped_urls,
+        })
+    }
+
+    // Fetches the given URL, following any redirects ourselves so that we can mark each URL along the way as visited.
+    // Returns the URL of the page eventually fetched along with the page itself. Each request made (e.g., for each
+    // redirect) waits for the rate limiter, if any.
+    fn fetch_following_redirects(
+        &self,
+        url: &Arc<Url>,
+        urls_visited: &mut dyn VisitedSet,
+        rate_limiter: &mut Option<RateLimiter>,
+    ) -> Result<(Arc<Url>, FetchedResource), FetchWebPageError> {
+        let mut url = Arc::clone(url);
+        let mut redirect_chain = vec![];
+        loop {
+            if self.www_fallback {
+                let fallback_host = url.host_str().and_then(|host| self.www_fallbacks.lock().unwrap().get(host).cloned());
+                if let Some(fallback_host) = fallback_host {
+                    let _ = Arc::make_mut(&mut url).set_host(Some(&fallback_host));
+                }
+            }
+            let fetch_url = self.fetch_url(&url);
+            if let Some(SchemeHandler::Custom(fetcher)) = self.fetchers.get(fetch_url.scheme()) {
+                if let Some(rate_limiter) = rate_limiter {
+                    rate_limiter.acquire();
+                }
+                let start = Instant::now();
+                return fetcher.fetch(&fetch_url)
+                    .and_then(|content| fetched_content(&fetch_url, content, &self.fetch_options(), start))
+                    .map(|mut fetched| {
+                        fetched.redirect_chain = redirect_chain;
+                        (url, fetched)
+                    });
+            }
+            let connection_times = if self.timing { self.probe_connection(&fetch_url) } else { None };
+            let fetched = match self.fetch_authorized(&fetch_url, rate_limiter) {
+                Err(FetchWebPageError::DnsError(err)) if self.www_fallback => {
+                    let fetched = self.fetch_www_fallback(Arc::make_mut(&mut url), err, rate_limiter);
+                    urls_visited.insert(&url);
+                    fetched
+                }
+                fetched => fetched,
+            };
+            match fetched {
+                Err(FetchWebPageError::Redirect(status, location)) => {
+                    // NOTE: relative redirects are resolved against the original URL, so they stay on its host, but
+                    // absolute ones to the rewritten host are followed (and reported) as they are.
+                    let location_url = url.join(&location).map(|mut location_url| {
+                        normalize_percent_encoding(&mut location_url);
+                        if self.sort_query_params {
+                            sort_query_params(&mut location_url);
+                        }
+                        location_url
+                    });
+                    let location_url = match location_url {
+                        Ok(location_url) if redirect_chain.len() < MAX_REDIRECTS && !urls_visited.contains(&location_url) => Arc::new(location_url),
+                        // Give up on redirects that are invalid, too long, or lead somewhere we've already been.
+                        _ => return Err(FetchWebPageError::Redirect(status, location)),
+                    };
+                    debug!("following redirect from {} to {}", url, location_url);
+                    urls_visited.insert(&location_url);
+                    redirect_chain.push((unshare_url(url), status));
+                    url = location_url;
+                }
+                fetched => return fetched.map(|mut fetched| {
+                    fetched.connection_times = connection_times;
+                    fetched.redirect_chain = redirect_chain;
+                    (url, fetched)
+                }),
+            }
+        }
+    }
+
+    // The URL to actually fetch the given one from.
+    fn fetch_url<'a>(&self, url: &'a Url) -> Cow<'a, Url> {
+        match &self.fetch_url_rewriter {
+            Some(fetch_url_rewriter) => Cow::Owned(fetch_url_rewriter(url)),
+            None => Cow::Borrowed(url),
+        }
+    }
+
+    // Fetches the given URL from its host with `www.` added (or removed) instead, after its host failed to resolve with
+    // the given error. If that host resolves, the URL is switched to it, as are those of any later fetches from the
+    // same host.
+    fn fetch_www_fallback(
+        &self,
+        url: &mut Url,
+        err: reqwest::Error,
+        rate_limiter: &mut Option<RateLimiter>,
+    ) -> Result<FetchedResource, FetchWebPageError> {
+        let host = match url.host_str() {
+            Some(host) => host.to_owned(),
+            None => return Err(FetchWebPageError::DnsError(err)),
+        };
+        let fallback_host = match host.strip_prefix("www.") {
+            Some(fallback_host) => fallback_host.to_owned(),
+            None => format!("www.{}", host),
+        };
+        let mut fallback_url = url.clone();
+        if fallback_url.set_host(Some(&fallback_host)).is_err() {
+            return Err(FetchWebPageError::DnsError(err));
+        }
+
+        debug!("failed to resolve {}; trying {}", host, fallback_host);
+        match self.fetch_authorized(&self.fetch_url(&fallback_url), rate_limiter) {
+            Err(FetchWebPageError::DnsError(_)) => Err(FetchWebPageError::DnsError(err)),
+            fetched => {
+                info!("falling back from {} to {}", host, fallback_host);
+                self.www_fallbacks.lock().unwrap().insert(host, fallback_host);
+                *url = fallback_url;
+                fetched
+            }
+        }
+    }
+
+    // Fetches the given URL, and fetches it once more if the bearer token (if any) is rejected, since the token
+    // provider may have refreshed it in the meantime.
+    fn fetch_authorized(&self, url: &Url, rate_limiter: &mut Option<RateLimiter>) -> Result<FetchedResource, FetchWebPageError> {
+        match self.fetch_cached(url, rate_limiter) {
+            Err(FetchWebPageError::BadHttpStatus(StatusCode::UNAUTHORIZED)) if self.token_provider.is_some() => {
+                debug!("bearer token rejected by {}; trying another", url);
+                self.fetch_cached(url, rate_limiter)
+            }
+            fetched => fetched,
+        }
+    }
+
+    // Fetches the given URL, conditionally if there's a cached response to the same request (and caching the response
+    // otherwise).
+    fn fetch_cached(&self, url: &Url, rate_limiter: &mut Option<RateLimiter>) -> Result<FetchedResource, FetchWebPageError> {
+        if let Some(rate_limiter) = rate_limiter {
+            rate_limiter.acquire();
+        }
+        let cache = match &self.response_cache {
+            Some(cache) => cache,
+            None => return fetch_web_page(self.request(Method::GET, url), &self.fetch_options()),
+        };
+        let start = Instant::now();
+        let cached = cache.get(url, &self.request_headers);
+        let request = match &cached {
+            Some(cached) => cached.validate(self.request(Method::GET, url)),
+            None => self.request(Method::GET, url),
+        };
+        let resp = request.send().map_err(classify_send_error)?;
+        let fetched = match cached {
+            Some(mut cached) if resp.status() == StatusCode::NOT_MODIFIED => {
+                debug!("{} not modified; using cached response", url);
+                // The cached response stands, but with whatever the server has updated since (e.g., a new `ETag`).
+                cached.refresh(resp.headers());
+                let CachedResponse { status, headers, content } = cached;
+                fetched_content(url, content, &self.fetch_options(), start).map(|mut fetched| {
+                    if let Some(html) = &mut fetched.html {
+                        html.status = status;
+                        html.headers = headers.clone();
+                    }
+                    fetched.status = status;
+                    fetched.http_version = resp.version();
+                    fetched.content_location = headers.get(header::CONTENT_LOCATION)
+                        .and_then(|location| location.to_str().ok())
+                        .map(str::to_owned);
+                    fetched.headers = headers;
+                    fetched
+                })
+            }
+            _ => read_web_page(resp, start, &self.fetch_options()),
+        };
+        if let Ok(fetched) = &fetched {
+            cache.insert(url, &self.request_headers, fetched);
+        }
+        fetched
+    }
+
+    fn fetch_options(&self) -> FetchOptions<'_> {
+        FetchOptions {
+            extra_content_types: &self.extra_content_types,
+            accepted_statuses: self.accepted_statuses.as_deref(),
+            keep_bodies: self.mirror_to.is_some(),
+            max_body_size: self.max_body_size,
+            skip_documents: self.skips_documents(),
+        }
+    }
+
+    // The frontier and visited set for a crawl when no others are configured.
+    fn default_frontier(&self) -> FifoFrontier {
+        FifoFrontier::with_capacity(self.frontier_capacity)
+    }
+
+    fn default_visited_set(&self) -> HashVisitedSet {
+        HashVisitedSet::with_capacity(self.visited_capacity)
+    }
+
+    // Whether pages are left for crawling to parse (if at all), rather than parsed as they're fetched.
+    fn skips_documents(&self) -> bool {
+        self.link_extraction == LinkExtraction::Streaming || self.parse_timeout.is_some()
+    }
+
+    // The robots rules for the origin of the given URL, which are fetched the first time they're needed.
+    fn robots(&self, url: &Url) -> Arc<Robots> {
+        let origin = url.origin().ascii_serialization();
+        if let Some(robots) = self.robots_cache.lock().unwrap().get(&origin) {
+            return robots.clone();
+        }
+        let robots = Arc::new(self.fetch_robots(url));
+        self.robots_cache.lock().unwrap().insert(origin, robots.clone());
+        robots
+    }
+
+    fn fetch_robots(&self, url: &Url) -> Robots {
+        let robots_url = match url.join("/robots.txt") {
+            Ok(robots_url) => robots_url,
+            Err(_) => return Robots::allow_all(),
+        };
+        let text = self.request(Method::GET, &robots_url).send()
+            .and_then(Response::error_for_status)
+            .and_then(|mut resp| resp.text());
+        match text {
+            Ok(text) => Robots::parse(&text, &robots_url),
+            // Without a `robots.txt` (or if it can't be fetched), anything goes.
+            Err(err) => {
+                debug!("no robots.txt at {}: {}", robots_url, err);
+                Robots::allow_all()
+            }
+        }
+    }
+
+    fn log_in(&self, login: &LoginConfig) -> Result<(), reqwest::Error> {
+        info!("logging in at {}", login.url);
+        let resp = self.request(Method::POST, &login.url).form(&login.form_fields).send()?;
+        debug!("logged in at {} with status {}", login.url, resp.status());
+        resp.error_for_status()?;
+        Ok(())
+    }
+
+    // Resolves the host of the given URL and connects to it, returning how long each took, as an estimate of how long
+    // they take for a request. The connection is closed straight away.
+    fn probe_connection(&self, url: &Url) -> Option<(Duration, Duration)> {
+        let start = Instant::now();
+        let addr = match self.resolved_addr(url) {
+            Some(addr) => addr,
+            None => url.to_socket_addrs().ok()?.next()?,
+        };
+        let dns = start.elapsed();
+        TcpStream::connect_timeout(&addr, self.connect_timeout.unwrap_or(DEFAULT_PROBE_TIMEOUT)).ok()?;
+        Some((dns, start.elapsed()))
+    }
+
+    // The address that the host of the given URL was overridden to resolve to, if any.
+    fn resolved_addr(&self, url: &Url) -> Option<SocketAddr> {
+        url.host_str().and_then(|host| {
+            self.resolve.iter().find(|(h, _)| h.eq_ignore_ascii_case(host)).map(|&(_, addr)| addr)
+        })
+    }
+
+    fn request(&self, method: Method, url: &Url) -> RequestBuilder {
+        let host_header = url.host_str().and_then(|host| {
+            self.host_headers.iter().find(|(h, _)| h.eq_ignore_ascii_case(host)).map(|(_, host_header)| &**host_header)
+        });
+        let request = match self.resolved_addr(url) {
+            Some(addr) => {
+                // Connect to the overridden address directly, but tell the server which host we really want.
+                let mut addr_url = url.clone();
+                let _ = addr_url.set_ip_host(addr.ip());
+                let _ = addr_url.set_port(Some(addr.port()));
+                let host_header = host_header.unwrap_or(&url[Position::BeforeHost..Position::AfterPort]);
+                self.client.request(method, addr_url).header(header::HOST, host_header)
+            }
+            None => {
+                let request = self.client.request(method, url.clone());
+                match host_header {
+                    Some(host_header) => request.header(header::HOST, host_header),
+                    None => request,
+                }
+            }
+        };
+        let request = request.headers(self.request_headers.clone());
+        match &self.token_provider {
+            Some(token_provider) => request.bearer_auth(token_provider()),
+            None => request,
+        }
+    }
+
+    // Applies the optional normalizations to the seed or a link, before checking whether it's been visited.
+    // NOTE: this isn't applied to redirects, since servers commonly redirect from the index file to the directory.
+    // Neither this nor any other normalization changes the case of paths, queries or fragments (which may well be
+    // case-sensitive), other than of the hex digits of percent-escapes; only the scheme and host are lowercased.
+    fn normalize_link(&self, url: &mut Url) {
+        if self.sort_query_params {
+            sort_query_params(url);
+        }
+        self.trailing_slash.apply(url);
+        if let Some(directory_index) = &self.directory_index {
+            if url.path().ends_with('/') {
+                let path = format!("{}{}", url.path(), directory_index);
+                url.set_path(&path);
+            }
+        }
+        if let Some(url_normalizer) = &self.url_normalizer {
+            *url = url_normalizer(url);
+        }
+    }
+
+    fn accepts_content_type(&self, content_type: &str) -> bool {
+        content_type == "text/html" || self.extra_content_types.iter().any(|pattern| mime_matches(pattern, content_type))
+    }
+
+    fn should_follow(&self, seed: &Url, url: &Url) -> bool {
+        if !self.fetchers.contains_key(url.scheme()) {
+            debug!("not following {}: no fetcher for scheme", url);
+            return false;
+        }
+
+        if !self.path_prefixes.is_empty() {
+            let in_prefix = host_key(url, self.treat_www_as_same) == host_key(seed, self.treat_www_as_same) &&
+                self.path_prefixes.iter().any(|prefix| url.path().starts_with(&**prefix));
+            if !in_prefix {
+                return false;
+            }
+        }
+
+        // The URL only has a port if it isn't the standard one.
+        // NOTE: the seed's own port is always allowed, or a site served on a non-standard port couldn't be crawled past
+        // its seed.
+        let port_allowed = match url.port() {
+            None => true,
+            Some(port) => seed.port() == Some(port) || self.allowed_ports.as_ref().is_some_and(|ports| ports.contains(&port)),
+        };
+        if !port_allowed {
+            debug!("not following {}: port not allowed", url);
+            return false;
+        }
+
+        if self.is_spider_trap(url) {
+            debug!("not following {}: looks like a spider trap", url);
+            return false;
+        }
+
+        true
+    }
+
+    // The most links that may be followed within the host of the given URL to reach it, if limited.
+    fn max_depth_for(&self, url: &Url) -> Option<usize> {
+        url.host_str()
+            .and_then(|host| self.max_depth_per_host.get(host))
+            .cloned()
+            .or(self.max_depth)
+    }
+
+    // Calendars, faceted search and the like can generate endless distinct URLs, which typically show up as very deep
+    // paths or paths that repeat the same segment over and over (e.g., `/a/a/a/...`).
+    fn is_spider_trap(&self, url: &Url) -> bool {
+        let segments: Vec<_> = url.path_segments().into_iter().flatten().filter(|segment| !segment.is_empty()).collect();
+        if self.max_path_depth.is_some_and(|max_depth| segments.len() > max_depth) {
+            return true;
+        }
+        if let Some(max_repeats) = self.max_path_segment_repeats {
+            let mut segment_counts = HashMap::new();
+            for segment in segments {
+                let count = segment_counts.entry(segment).or_insert(0);
+                *count += 1;
+                if *count > max_repeats {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
+impl Default for Crawler {
+    fn default() -> Crawler {
+        Crawler::new()
+    }
+}
+
+impl CrawlerBuilder {
+    // Only follows links on the seed's host whose path starts with one of the given prefixes.
+    pub fn path_prefix(mut self, prefix: impl Into<String>) -> CrawlerBuilder {
+        self.path_prefixes.push(prefix.into());
+        self
+    }
+
+    // Keeps links to fragments of the same page (e.g., `#top`) in `WebPageInfo::links`. They are never crawled.
+    pub fn keep_fragment_links(mut self, keep: bool) -> CrawlerBuilder {
+        self.keep_fragment_links = keep;
+        self
+    }
+
+    // Doesn't fetch pages that the `robots.txt` of their host disallows.
+    pub fn respect_robots_txt(mut self, respect: bool) -> CrawlerBuilder {
+        self.respect_robots_txt = respect;
+        self
+    }
+
+    // Treats URLs that differ only in the order of their query parameters (e.g., `?a=1&b=2` and `?b=2&a=1`) as the same
+    // page, which is then fetched with its parameters sorted.
+    pub fn sort_query_params(mut self, sort: bool) -> CrawlerBuilder {
+        self.sort_query_params = sort;
+        self
+    }
+
+    // Adds or strips the trailing slashes of paths, so that each page is visited (and fetched) under only one of them.
+    pub fn trailing_slash(mut self, trailing_slash: TrailingSlash) -> CrawlerBuilder {
+        self.trailing_slash = trailing_slash;
+        self
+    }
+
+    // Appends the given file name (e.g., `index.html`) to the paths of URLs that end in a slash before fetching them.
+    pub fn directory_index(mut self, file_name: impl Into<String>) -> CrawlerBuilder {
+        self.directory_index = Some(file_name.into());
+        self
+    }
+
+    // Doesn't follow links whose `type` attribute declares a content type that wouldn't be accepted anyway (i.e., other
+    // than HTML or the extra content types), so that they're skipped without even being requested.
+    pub fn respect_link_types(mut self, respect: bool) -> CrawlerBuilder {
+        self.respect_link_types = respect;
+        self
+    }
+
+    // Sends requests for the given host to `addr` rather than resolving it. Since the URL's host is only passed
+    // in the `Host` header, this is not suitable for HTTPS, where certificates are checked against the address.
+    pub fn resolve(mut self, host: impl Into<String>, addr: SocketAddr) -> CrawlerBuilder {
+        self.resolve.push((host.into(), addr));
+        self
+    }
+
+    // Sends the given `Host` header (e.g., naming a virtual host behind a load balancer) with requests for the given
+    // host, which is still the one connected to (or the address it's resolved to by `resolve`).
+    // NOTE: the client has no way of setting the server name sent with TLS (SNI), which is always the URL's host (and
+    // none at all when connecting to an address, as with `resolve`).
+    pub fn host_header(mut self, host: impl Into<String>, host_header: impl Into<String>) -> CrawlerBuilder {
+        self.host_headers.push((host.into(), host_header.into()));
+        self
+    }
+
+    // Sends the given header with every request (replacing any the client would send by default).
+    pub fn request_header(mut self, name: HeaderName, value: HeaderValue) -> CrawlerBuilder {
+        self.request_headers.insert(name, value);
+        self
+    }
+
+    // Keeps the pages fetched (that have an `ETag` or `Last-Modified`) in the given cache, and requests those already
+    // in it conditionally, using the cached page if the server says it's not modified.
+    pub fn response_cache(mut self, cache: ResponseCache) -> CrawlerBuilder {
+        self.response_cache = Some(cache);
+        self
+    }
+
+    // Keeps the body of each page in `CrawlResult::raw_html`.
+    pub fn keep_raw_html(mut self, keep: bool) -> CrawlerBuilder {
+        self.keep_raw_html = keep;
+        self
+    }
+
+    // Makes `crawl` return an error if the seed can't be fetched, instead of a crawl that yields nothing. Failing to
+    // fetch any other page never fails a crawl.
+    pub fn fail_on_seed_error(mut self, fail: bool) -> CrawlerBuilder {
+        self.fail_on_seed_error = fail;
+        self
+    }
+
+    // Records a `Timing` for each result, whose DNS and connect times are estimated by opening an extra connection to
+    // the host before each request (so doubling the connections made).
+    pub fn timing(mut self, timing: bool) -> CrawlerBuilder {
+        self.timing = timing;
+        self
+    }
+
+    // Saves the body of each page fetched under the given directory, as `dir/host/path`. Other resources (e.g.,
+    // images) are only saved if they're fetched at all, i.e., if their content types were added via `extra_content_type`.
+    pub fn mirror_to(mut self, dir: impl Into<PathBuf>) -> CrawlerBuilder {
+        self.mirror_to = Some(dir.into());
+        self
+    }
+
+    // Takes the URLs of links (`<a>` and `<area>` elements) from these attributes instead of just `href`, e.g. to
+    // include `data-href` for sites that navigate with JavaScript. Each attribute a link has is a link of its own.
+    pub fn link_attributes(mut self, attributes: impl IntoIterator<Item = impl Into<String>>) -> CrawlerBuilder {
+        self.link_attributes = Some(attributes.into_iter().map(Into::into).collect());
+        self
+    }
+
+    // Treats `www.example.com` as the same host as `example.com`, both when restricting the crawl to the seed's host
+    // and when counting distinct hosts. Links to the `www.` variant of the seed's host (or vice versa) are crawled on
+    // the seed's host, so its pages aren't crawled twice.
+    pub fn treat_www_as_same(mut self, treat_www_as_same: bool) -> CrawlerBuilder {
+        self.treat_www_as_same = treat_www_as_same;
+        self
+    }
+
+    // Stops following links to new hosts once this many distinct hosts have been encountered.
+    pub fn max_hosts(mut self, max_hosts: usize) -> CrawlerBuilder {
+        self.max_hosts = Some(max_hosts);
+        self
+    }
+
+    // Waits at least this long between consecutive requests to the same host.
+    pub fn request_delay(mut self, delay: Duration) -> CrawlerBuilder {
+        self.request_delay = delay;
+        self
+    }
+
+    // Makes at most this many (which must be positive) requests a second on average across all hosts, while allowing
+    // bursts of up to a second's worth after a lull. Every request counts, including redirects followed and retries.
+    pub fn rate_limit(mut self, requests_per_second: f64) -> CrawlerBuilder {
+        self.rate_limit = Some(requests_per_second);
+        self
+    }
+
+    // Retries a page up to this many times if fetching it fails in a way that may be temporary (e.g., a timeout or a
+    // server error).
+    pub fn max_retries(mut self, max_retries: usize) -> CrawlerBuilder {
+        self.max_retries = max_retries;
+        self
+    }
+
+    // Stops retrying pages once this many retries have been made in the crawl as a whole, which bounds the time spent
+    // on a failing site.
+    pub fn max_total_retries(mut self, max_retries: usize) -> CrawlerBuilder {
+        self.max_total_retries = Some(max_retries);
+        self
+    }
+
+    // Gives up connecting to a host after this long.
+    pub fn connect_timeout(mut self, timeout: Duration) -> CrawlerBuilder {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    // Gives up on a request (including connecting, and reading the response) after this long.
+    pub fn timeout(mut self, timeout: Duration) -> CrawlerBuilder {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    // Extracts links without building a document (and without a timeout) if streaming, in which case soft 404s and
+    // mixed content (other than images) can't be detected, and the `article:modified_time` of pages isn't known.
+    pub fn link_extraction(mut self, link_extraction: LinkExtraction) -> CrawlerBuilder {
+        self.link_extraction = link_extraction;
+        self
+    }
+
+    // Skips pages that take longer than this to parse and extract the links and such from, so that pathological HTML
+    // can't stall the crawl. Each page is then parsed on a thread of its own, and its document isn't yielded (by
+    // `Crawler::crawl_with_documents`).
+    pub fn parse_timeout(mut self, timeout: Duration) -> CrawlerBuilder {
+        self.parse_timeout = Some(timeout);
+        self
+    }
+
+    // Adds an extra delay between requests to a host that fails or reports being overloaded, starting at `initial`
+    // and doubling with each further failure up to `max`, then halving with each success.
+    pub fn adaptive_delay(mut self, initial: Duration, max: Duration) -> CrawlerBuilder {
+        self.adaptive_delay = Some(AdaptiveDelay {
+            initial,
+            max,
+        });
+        self
+    }
+
+    // Skips a host's URLs for `cooldown` after `max_failures` consecutive failed requests to it, then tries a single
+    // request again before deciding whether to keep skipping it.
+    pub fn circuit_breaker(mut self, max_failures: usize, cooldown: Duration) -> CrawlerBuilder {
+        self.circuit_breaker = Some(CircuitBreaker {
+            max_failures,
+            cooldown,
+        });
+        self
+    }
+
+    // Also records (but doesn't parse) resources of the given MIME type, which may be a pattern such as `image/*`.
+    pub fn extra_content_type(mut self, content_type: impl Into<String>) -> CrawlerBuilder {
+        self.extra_content_types.push(content_type.into());
+        self
+    }
+
+    // Only accepts pages with these statuses, instead of any successful (2xx) status, so include `200 OK` if wanted.
+    pub fn accepted_statuses(mut self, statuses: impl IntoIterator<Item = StatusCode>) -> CrawlerBuilder {
+        self.accepted_statuses = Some(statuses.into_iter().collect());
+        self
+    }
+
+    // Also follows links to URLs on the given ports, instead of only those on the standard port for their scheme (e.g.,
+    // 80 for HTTP) or that of the seed, as by default.
+    pub fn allowed_ports(mut self, ports: impl IntoIterator<Item = u16>) -> CrawlerBuilder {
+        self.allowed_ports = Some(ports.into_iter().collect());
+        self
+    }
+
+    // Stops the crawl after this many results.
+    pub fn max_pages(mut self, max_pages: usize) -> CrawlerBuilder {
+        self.max_pages = Some(max_pages);
+        self
+    }
+
+    // Stops the crawl once the bodies fetched add up to this many bytes (or more, since the page that crosses the limit
+    // is still fetched in full).
+    pub fn max_total_bytes(mut self, max_bytes: usize) -> CrawlerBuilder {
+        self.max_total_bytes = Some(max_bytes);
+        self
+    }
+
+    // Doesn't follow links more than this many links away from where the crawl first entered their host (i.e., the
+    // seed, or the first page linked to on another host), unless the host has a limit of its own. Links that leave the
+    // host and come back count too, so that alternating between hosts doesn't get around the limit.
+    pub fn max_depth(mut self, max_depth: usize) -> CrawlerBuilder {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    // Limits the depth of the given host, as for `max_depth`, instead of the default limit (if any).
+    pub fn max_depth_for_host(mut self, host: impl Into<String>, max_depth: usize) -> CrawlerBuilder {
+        self.max_depth_per_host.insert(host.into().to_ascii_lowercase(), max_depth);
+        self
+    }
+
+    // Skips pages and resources whose bodies are larger than this many bytes, which are counted as they're received
+    // (so chunked responses are limited too), and stops receiving them at that point.
+    pub fn max_body_size(mut self, max_size: usize) -> CrawlerBuilder {
+        self.max_body_size = Some(max_size);
+        self
+    }
+
+    // Stops fetching pages from any one host after this many results from it.
+    pub fn max_pages_per_host(mut self, max_pages: usize) -> CrawlerBuilder {
+        self.max_pages_per_host = Some(max_pages);
+        self
+    }
+
+    // Doesn't follow links whose paths have more than the given number of segments.
+    pub fn max_path_depth(mut self, max_depth: usize) -> CrawlerBuilder {
+        self.max_path_depth = Some(max_depth);
+        self
+    }
+
+    // Doesn't follow links whose paths contain any one segment more than the given number of times.
+    pub fn max_path_segment_repeats(mut self, max_repeats: usize) -> CrawlerBuilder {
+        self.max_path_segment_repeats = Some(max_repeats);
+        self
+    }
+
+    // Stops following the pages of a paginated listing (i.e., links that differ only in the given query parameter, such
+    // as `page`) once the given number of its pages in a row have had the same content, as an endless paginator's do
+    // past the end. The content of a page is taken to be its links other than to pages of the same listing.
+    pub fn pagination_loop(mut self, param: impl Into<String>, threshold: usize) -> CrawlerBuilder {
+        self.pagination_loop = Some(PaginationLoop {
+            param: param.into(),
+            threshold,
+        });
+        self
+    }
+
+    // Queues at most this many URLs at a time, dropping any links found while the frontier is full (which are counted
+    // by `Crawl::dropped_urls`), so that a site with endless links can't use up memory. Dropped links aren't marked
+    // as visited (nor their hosts counted towards `max_hosts`), so they're still followed if found again once there's
+    // room.
+    pub fn max_frontier(mut self, max_urls: usize) -> CrawlerBuilder {
+        self.max_frontier = Some(max_urls);
+        self
+    }
+
+    // Pre-sizes the default frontier for this many URLs, to save growing it during big crawls.
+    pub fn with_frontier_capacity(mut self, capacity: usize) -> CrawlerBuilder {
+        self.frontier_capacity = capacity;
+        self
+    }
+
+    // Pre-sizes the default visited set for this many URLs, to save growing it during big crawls.
+    pub fn with_visited_capacity(mut self, capacity: usize) -> CrawlerBuilder {
+        self.visited_capacity = capacity;
+        self
+    }
+
+    // Uses frontiers created by the given function instead of the default (breadth-first) `FifoFrontier`.
+    pub fn frontier(mut self, frontier: impl Fn() -> Box<dyn Frontier> + Send + Sync + 'static) -> CrawlerBuilder {
+        self.frontier = Some(Callback(Arc::new(frontier)));
+        self
+    }
+
+    // Uses visited sets created by the given function instead of the default `HashVisitedSet`.
+    pub fn visited_set(mut self, visited_set: impl Fn() -> Box<dyn VisitedSet> + Send + Sync + 'static) -> CrawlerBuilder {
+        self.visited_set = Some(Callback(Arc::new(visited_set)));
+        self
+    }
+
+    // If a host fails to resolve, tries it with `www.` added (or removed) instead, and then fetches (and reports) its
+    // pages from that host.
+    pub fn www_fallback(mut self, fallback: bool) -> CrawlerBuilder {
+        self.www_fallback = fallback;
+        self
+    }
+
+    // Counts the given URLs as visited before each crawl begins (e.g., because they were indexed by an earlier crawl), so
+    // that links to them aren't followed. The seed is crawled regardless.
+    pub fn preseed_visited(mut self, urls: impl IntoIterator<Item = Url>) -> CrawlerBuilder {
+        self.preseeded_urls.extend(urls);
+        self
+    }
+
+    // Only fetches (and follows the links of) pages for which the given function returns true, e.g. so that pages
+    // known to be unchanged since an earlier crawl can be skipped. The seed is subject to it too.
+    pub fn should_process(mut self, should_process: impl Fn(&Url) -> bool + Send + Sync + 'static) -> CrawlerBuilder {
+        self.should_process = Some(Callback(Arc::new(should_process)));
+        self
+    }
+
+    // Calls the given function with the URL and a hash of the body of each page fetched (and of each other resource
+    // whose body is kept), e.g. to store it for `should_process` on a later crawl.
+    pub fn on_hashed(mut self, on_hashed: impl Fn(&Url, u64) + Send + Sync + 'static) -> CrawlerBuilder {
+        self.on_hashed = Some(Callback(Arc::new(on_hashed)));
+        self
+    }
+
+    // Maps the seed and each link to another URL (e.g., its canonical form) before checking whether it's been visited,
+    // after the built-in normalization.
+    pub fn url_normalizer(mut self, url_normalizer: impl Fn(&Url) -> Url + Send + Sync + 'static) -> CrawlerBuilder {
+        self.url_normalizer = Some(Callback(Arc::new(url_normalizer)));
+        self
+    }
+
+    // Fetches URLs of the given scheme (e.g., `file`) with the given fetcher, so that they can be crawled (or linked to)
+    // too. The crawler's own client fetches HTTP(S) URLs unless another fetcher is given for them.
+    // NOTE: only the crawl itself uses custom fetchers; anything else (e.g., `robots.txt` and logging in) still uses
+    // the client.
+    pub fn fetcher(mut self, scheme: impl Into<String>, fetcher: impl Fetcher + 'static) -> CrawlerBuilder {
+        let fetcher: Arc<dyn Fetcher> = Arc::new(fetcher);
+        self.fetchers.insert(scheme.into().to_ascii_lowercase(), SchemeHandler::Custom(Callback(fetcher)));
+        self
+    }
+
+    // Sends an `Authorization: Bearer` header with every request, with the token that the given function returns at the
+    // time, so that short-lived tokens can be refreshed mid-crawl. A request that's rejected as unauthorized (401) is
+    // retried once, with a token from another call.
+    pub fn token_provider(mut self, token_provider: impl Fn() -> String + Send + Sync + 'static) -> CrawlerBuilder {
+        self.token_provider = Some(Callback(Arc::new(token_provider)));
+        self
+    }
+
+    // Fetches each page from the URL that the given function maps it to (e.g., on a staging host) instead, while still
+    // reporting (and resolving its links against) the original URL. The `robots.txt` of the original host still
+    // applies.
+    pub fn fetch_url_rewriter(mut self, rewriter: impl Fn(&Url) -> Url + Send + Sync + 'static) -> CrawlerBuilder {
+        self.fetch_url_rewriter = Some(Callback(Arc::new(rewriter)));
+        self
+    }
+
+    // Uses a `HostRoundRobinFrontier`, so that requests to different hosts are interleaved, while respecting the
+    // request delay for each host.
+    pub fn interleave_hosts(mut self, interleave: bool) -> CrawlerBuilder {
+        self.interleave_hosts = interleave;
+        self
+    }
+
+    // Yields results in the order their URLs were discovered, even if a frontier (e.g., with `interleave_hosts`) fetches
+    // them in another order. Results may be held back until the pages discovered before them have been fetched.
+    pub fn ordered(mut self, ordered: bool) -> CrawlerBuilder {
+        self.ordered = ordered;
+        self
+    }
+
+    // Posts the given form before crawling, keeping any session cookies it sets for the rest of the crawl.
+    pub fn login(mut self, login: LoginConfig) -> CrawlerBuilder {
+        self.login = Some(login);
+        self
+    }
+
+    // Flags pages that look like error pages despite a successful status, and doesn't follow their links.
+    pub fn soft_404(mut self, soft_404: Soft404Config) -> CrawlerBuilder {
+        self.soft_404 = Some(soft_404);
+        self
+    }
+
+    // Uses the given client instead of building one. Client-level options (e.g., timeouts, the user agent and the
+    // cookie store needed by `login`) then come from that client, and any redirects it follows itself are invisible
+    // to the crawler.
+    // NOTE: there's no option to force HTTP/1.1, as reqwest 0.9 can't turn HTTP/2 off on a client. Our own client only
+    // ever speaks HTTP/1.1 anyway (reqwest only negotiates HTTP/2 via ALPN with rustls, which isn't enabled), so
+    // HTTP/2 is only used by an injected client built with `h2_prior_knowledge`.
+    pub fn client(mut self, client: Client) -> CrawlerBuilder {
+        self.client = Some(client);
+        self
+    }
+
+    // Makes the order of results depend only on the site being crawled, not on timing, which is handy for tests. In
+    // particular, `interleave_hosts` then simply takes turns between hosts, and hosts on which the circuit breaker
+    // trips are skipped for the rest of the crawl.
+    pub fn deterministic(mut self, deterministic: bool) -> CrawlerBuilder {
+        self.deterministic = deterministic;
+        self
+    }
+
+    pub fn build(self) -> Result<Crawler, reqwest::Error> {
+        let client = match self.client {
+            Some(client) => client,
+            // We follow redirects ourselves.
+            None => Client::builder()
+                .redirect(RedirectPolicy::none())
+                .cookie_store(self.login.is_some())
+                .connect_timeout(self.connect_timeout)
+                .timeout(self.timeout)
+                .build()?,
+        };
+        let frontier_delay = if self.deterministic { Duration::from_secs(0) } else { self.request_delay };
+        let frontier = match self.frontier {
+            None if self.interleave_hosts => Some(Callback(Arc::new(move || {
+                Box::new(HostRoundRobinFrontier::new(frontier_delay)) as Box<dyn Frontier>
+            }) as Arc<FrontierFactory>)),
+            frontier => frontier,
+        };
+        let deterministic = self.deterministic;
+        let circuit_breaker = self.circuit_breaker.map(|circuit_breaker| CircuitBreaker {
+            cooldown: if deterministic { Duration::from_secs(u64::MAX) } else { circuit_breaker.cooldown },
+            ..circuit_breaker
+        });
+
+        Ok(Crawler {
+            client,
+            path_prefixes: self.path_prefixes,
+            keep_fragment_links: self.keep_fragment_links,
+            link_attributes: self.link_attributes
+                .unwrap_or_else(|| DEFAULT_LINK_ATTRIBUTES.iter().map(|&attribute| attribute.to_owned()).collect()),
+            treat_www_as_same: self.treat_www_as_same,
+            sort_query_params: self.sort_query_params,
+            trailing_slash: self.trailing_slash,
+            directory_index: self.directory_index,
+            respect_robots_txt: self.respect_robots_txt,
+            respect_link_types: self.respect_link_types,
+            resolve: self.resolve,
+            host_headers: self.host_headers,
+            request_headers: self.request_headers,
+            response_cache: self.response_cache,
+            keep_raw_html: self.keep_raw_html,
+            fail_on_seed_error: self.fail_on_seed_error,
+            timing: self.timing,
+            mirror_to: self.mirror_to,
+            max_hosts: self.max_hosts,
+            request_delay: self.request_delay,
+            rate_limit: self.rate_limit,
+            max_retries: self.max_retries,
+            max_total_retries: self.max_total_retries,
+            max_frontier: self.max_frontier,
+            connect_timeout: self.connect_timeout,
+            parse_timeout: self.parse_timeout,
+            link_extraction: self.link_extraction,
+            adaptive_delay: self.adaptive_delay,
+            circuit_breaker,
+            extra_content_types: self.extra_content_types,
+            accepted_statuses: self.accepted_statuses,
+            allowed_ports: self.allowed_ports,
+            max_pages: self.max_pages,
+            max_pages_per_host: self.max_pages_per_host,
+            max_total_bytes: self.max_total_bytes,
+            max_depth: self.max_depth,
+            max_depth_per_host: self.max_depth_per_host,
+            max_body_size: self.max_body_size,
+            max_path_depth: self.max_path_depth,
+            max_path_segment_repeats: self.max_path_segment_repeats,
+            pagination_loop: self.pagination_loop,
+            frontier_capacity: self.frontier_capacity,
+            visited_capacity: self.visited_capacity,
+            frontier,
+            visited_set: self.visited_set,
+            preseeded_urls: self.preseeded_urls,
+            url_normalizer: self.url_normalizer,
+            fetch_url_rewriter: self.fetch_url_rewriter,
+            token_provider: self.token_provider,
+            fetchers: {
+                let mut fetchers: HashMap<_, _> = ["http", "https"].iter()
+                    .map(|&scheme| (scheme.to_owned(), SchemeHandler::Http))
+                    .collect();
+                fetchers.extend(self.fetchers);
+                fetchers
+            },
+            should_process: self.should_process,
+            on_hashed: self.on_hashed,
+            login: self.login,
+            soft_404: self.soft_404,
+            ordered: self.ordered,
+            cancel_handle: CancelHandle::default(),
+            robots_cache: Arc::default(),
+            www_fallback: self.www_fallback,
+            www_fallbacks: Arc::default(),
+        })
+    }
+}
+
+// The host of the given URL, without any leading `www.` if that's to be ignored.
+fn host_key(url: &Url, treat_www_as_same: bool) -> Option<&str> {
+    let host = url.host_str()?;
+    match host.get(..4) {
+        Some(prefix) if treat_www_as_same && prefix.eq_ignore_ascii_case("www.") => Some(&host[4..]),
+        _ => Some(host),
+    }
+}
+
+// The given shared URL as a URL of its own, which only needs cloning if it's still shared.
+fn unshare_url(url: Arc<Url>) -> Url {
+    Arc::try_unwrap(url).unwrap_or_else(|url| Url::clone(&url))
+}
+
+// Switches the given URL to the host of the seed if it's a `www.` variant of it (or vice versa), so that the same page
+// isn't crawled twice under both hosts.
+fn move_to_seed_host(seed: &Url, url: &mut Url) {
+    if url.host_str() != seed.host_str() && host_key(url, true) == host_key(seed, true) {
+        let _ = url.set_host(seed.host_str());
+    }
+}
+
+fn is_same_page_fragment(page_url: &Url, url: &Url) -> bool {
+    url.fragment().is_some() && url[..Position::AfterQuery] == page_url[..Position::AfterQuery]
+}
+
+// Sorts the given results so that each page comes before the pages it links to. Ties are broken by crawl order, and
+// cycles are broken at the earliest-crawled page left, so the order is stable.
+fn sort_topologically(results: Vec<CrawlResult>) -> Vec<CrawlResult> {
+    let count = results.len();
+    let children: Vec<Vec<usize>> = {
+        let indices: HashMap<&Url, usize> = results.iter().enumerate().map(|(i, result)| (&*result.url, i)).collect();
+        results.iter().enumerate().map(|(i, result)| {
+            let mut children: Vec<usize> = result.page.iter()
+                .flat_map(|page| &page.links)
+                .filter_map(|link_url| {
+                    let mut link_url = link_url.clone();
+                    link_url.set_fragment(None);
+                    indices.get(&link_url).cloned()
+                })
+                .filter(|&j| j != i)
+                .collect();
+            children.sort();
+            children.dedup();
+            children
+        }).collect()
+    };
+
+    let mut in_degrees = vec![0; count];
+    for &j in children.iter().flatten() {
+        in_degrees[j] += 1;
+    }
+    let mut ready: BTreeSet<usize> = (0..count).filter(|&i| in_degrees[i] == 0).collect();
+    let mut sorted = vec![false; count];
+    let mut order = Vec::with_capacity(count);
+    while order.len() < count {
+        // If nothing is ready, we're in a cycle.
+        let i = match ready.iter().next().cloned() {
+            Some(i) => {
+                ready.remove(&i);
+                i
+            }
+            None => (0..count).find(|&i| !sorted[i]).unwrap(),
+        };
+        sorted[i] = true;
+        order.push(i);
+        for &j in &children[i] {
+            if !sorted[j] {
+                in_degrees[j] -= 1;
+                if in_degrees[j] == 0 {
+                    ready.insert(j);
+                }
+            }
+        }
+    }
+
+    let mut results: Vec<_> = results.into_iter().map(Some).collect();
+    order.into_iter().map(|i| results[i].take().unwrap()).collect()
+}
+
+pub fn crawl_web_page(url: impl IntoUrl) -> impl Iterator<Item = (Url, WebPageInfo)> {
+    Crawler::new().crawl(url).into_iter().flatten().filter_map(|result| Some((unshare_url(result.url), result.page?)))
+}
+
+// NOTE: ideally the test harness would spawn a temporary local HTTP server so as not to rely on the Web.
+#[cfg(test)]
+mod tests {
+    use is_match::is_match;
+
+    use super::*;
+    use crate::test_server::{BlackHole, Response, TestServer};
+
+    use select::predicate::Class;
+
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::sync::atomic::AtomicBool;
+
+    #[test]
+    fn test_fetch_web_page() {
+        let client = Client::new();
+        assert!(fetch_web_page(client.get("http://google.com/"), &FetchOptions::default()).is_ok());
+        assert!(fetch_web_page(client.get("http://bing.com/"), &FetchOptions::default()).is_ok());
+        assert!(fetch_web_page(client.get("https://en.wikipedia.org/wiki/Rust_(programming_language)"), &FetchOptions::default()).is_ok());
+
+        assert!(is_match!(fetch_web_page(client.get("http://not.a.domain/"), &FetchOptions::default()), Err(FetchWebPageError::DnsError(_))));
+
+        assert!(is_match!(fetch_web_page(client.get("http://google.com/not_a_valid_url"), &FetchOptions::default()), Err(FetchWebPageError::BadHttpStatus(StatusCode::NOT_FOUND))));
+
+        // TODO: test other sorts of errors here.
+    }
+
+    #[test]
+    fn test_web_page_info() {
+        let doc = fetch_web_page(Client::new().get("http://rust-lang.org/"), &FetchOptions::default()).unwrap().html.unwrap().document;
+        let doc_info = get_web_page_info(&"http://rust-lang.org/".parse().unwrap(), &doc).unwrap();
+        assert_eq!(doc_info.title, "Rust Programming Language");
+        assert!(doc_info.links.contains(&"https://blog.rust-lang.org/".parse().unwrap()));
+        assert!(doc_info.links.contains(&"https://doc.rust-lang.org/".parse().unwrap()));
+        assert!(doc_info.links.contains(&"https://users.rust-lang.org/".parse().unwrap()));
+
+        // TODO: test info retrieved from other websites.
+
+        // TODO: check for web page with no title.
+    }
+
+    #[test]
+    fn test_fetch() {
+        let server = TestServer::start(|req| match &*req.path {
+            "/" => Response::html(r#"<title>Home</title><a href="/about">About</a>"#).header("X-Test", "yes"),
+            "/image.png" => Response::new(200).header("Content-Type", "image/png"),
+            _ => Response::not_found(),
+        });
+        let client = Client::new();
+
+        let page = fetch(server.url("/"), &client).unwrap();
+        assert_eq!(page.final_url, server.url("/"));
+        assert_eq!(page.status, StatusCode::OK);
+        assert_eq!(page.headers["X-Test"], "yes");
+        assert_eq!(get_web_page_info(&page.final_url, &page.document).unwrap().title, "Home");
+
+        assert!(is_match!(fetch(server.url("/image.png"), &client), Err(FetchWebPageError::BadContentType(_))));
+        assert!(is_match!(fetch(server.url("/missing"), &client), Err(FetchWebPageError::BadHttpStatus(StatusCode::NOT_FOUND))));
+    }
+
+    #[test]
+    fn test_web_page_info_area_links() {
+        let doc = Document::from(r##"
+            <html>
+                <head><title>Gallery</title></head>
+                <body>
+                    <img src="map.png" usemap="#map">
+                    <map name="map">
+                        <area shape="rect" coords="0,0,10,10" href="first.html">
+                        <area shape="rect" coords="10,10,20,20" href="/gallery/second.html">
+                        <area shape="rect" coords="20,20,30,30" alt="No link">
+                    </map>
+                </body>
+            </html>
+        "##);
+        let doc_info = get_web_page_info(&"http://example.com/gallery/index.html".parse().unwrap(), &doc).unwrap();
+        assert_eq!(doc_info.links, [
+            "http://example.com/gallery/first.html".parse().unwrap(),
+            "http://example.com/gallery/second.html".parse::<Url>().unwrap(),
+        ]);
+    }
+
+    #[test]
+    fn test_web_page_info_protocol_relative_links() {
+        let doc = Document::from(r#"
+            <title>Protocol-relative</title>
+            <a href="//cdn.example.com/x">CDN</a> <a href="//example.org:8080/y?z">Elsewhere</a>
+        "#);
+        let doc_info = get_web_page_info(&"https://example.com/page".parse().unwrap(), &doc).unwrap();
+        assert_eq!(doc_info.links, [
+            "https://cdn.example.com/x".parse().unwrap(),
+            "https://example.org:8080/y?z".parse::<Url>().unwrap(),
+        ]);
+    }
+
+    #[test]
+    fn test_web_page_info_duplicate_links() {
+        let doc = Document::from(r#"
+            <title>Duplicates</title>
+            <a href="/a">A</a> <a href="/b">B</a> <a href="/a">A again</a>
+            <a href="http://example.com/a">A, absolutely</a> <a href="/b">B again</a> <a href="/c">C</a>
+        "#);
+        let doc_info = get_web_page_info(&"http://example.com/".parse().unwrap(), &doc).unwrap();
+        assert_eq!(doc_info.links, [
+            "http://example.com/a".parse().unwrap(),
+            "http://example.com/b".parse().unwrap(),
+            "http://example.com/c".parse::<Url>().unwrap(),
+        ]);
+    }
+
+    #[test]
+    fn test_web_page_info_images() {
+        let doc = Document::from(r#"
+            <title>Images</title>
+            <img src="logo.png" alt="Logo">
+            <img src="/photo-320.jpg"
+                 srcset="/photo-320.jpg 320w, /photo-640.jpg 640w,/photo-1280.jpg   1280w">
+            <picture>
+                <source srcset="https://cdn.example.com/resize/w=400,h=300/photo.webp 1x, https://cdn.example.com/resize/w=800,h=600/photo.webp 2x">
+                <source srcset="/photo.avif, /photo@2x.avif 2x">
+                <img src="/photo.jpg">
+            </picture>
+            <video><source src="/movie.mp4"></video>
+        "#);
+        let doc_info = get_web_page_info(&"http://example.com/gallery/".parse().unwrap(), &doc).unwrap();
+        let images: Vec<_> = doc_info.images.iter().map(Url::as_str).collect();
+        assert_eq!(images, [
+            "http://example.com/gallery/logo.png",
+            "http://example.com/photo-320.jpg",
+            "http://example.com/photo-640.jpg",
+            "http://example.com/photo-1280.jpg",
+            "https://cdn.example.com/resize/w=400,h=300/photo.webp",
+            "https://cdn.example.com/resize/w=800,h=600/photo.webp",
+            "http://example.com/photo.avif",
+            "http://example.com/photo@2x.avif",
+            "http://example.com/photo.jpg",
+        ]);
+    }
+
+    #[test]
+    fn test_web_page_info_link_elements() {
+        let doc = Document::from(r#"
+            <head>
+                <title>Links</title>
+                <link rel="stylesheet" href="/style.css">
+                <link rel="shortcut icon" href="favicon.ico">
+                <link rel="alternate" type="application/rss+xml" href="https://example.com/feed.xml">
+                <link rel="preload">
+            </head>
+        "#);
+        let doc_info = get_web_page_info(&"http://example.com/blog/".parse().unwrap(), &doc).unwrap();
+        assert_eq!(doc_info.link_elements, [
+            ("stylesheet".to_owned(), "http://example.com/style.css".parse().unwrap()),
+            ("shortcut icon".to_owned(), "http://example.com/blog/favicon.ico".parse().unwrap()),
+            ("alternate".to_owned(), "https://example.com/feed.xml".parse::<Url>().unwrap()),
+        ]);
+    }
+
+    #[test]
+    fn test_web_page_info_semantically_equal() {
+        let url = "http://example.com/".parse().unwrap();
+        let doc_info = get_web_page_info(&url, &Document::from(r#"
+            <title>Links</title><a href="/a">A</a> <a href="/b">B</a> <a href="/c">C</a>
+        "#)).unwrap();
+        let reordered_doc_info = get_web_page_info(&url, &Document::from(r#"
+            <title>Links</title><a href="/c">C</a> <a href="/a">A</a> <a href="/b">B</a> <a href="/a">A again</a>
+        "#)).unwrap();
+        let other_doc_info = get_web_page_info(&url, &Document::from(r#"
+            <title>Links</title><a href="/a">A</a> <a href="/b">B</a>
+        "#)).unwrap();
+        assert_ne!(doc_info, reordered_doc_info);
+        assert!(doc_info.semantically_equal(&reordered_doc_info));
+        assert!(!doc_info.semantically_equal(&other_doc_info));
+    }
+
+    #[test]
+    fn test_crawl_percent_encoding() {
+        let server = TestServer::start(|req| match &*req.path {
+            "/" => Response::html(r#"
+                <title>Home</title>
+                <a href="/path with space">1</a> <a href="/path%20with%20space">2</a>
+                <a href="/café">3</a> <a href="/caf%c3%a9">4</a> <a href="/caf%C3%A9">5</a>
+                <a href="/%7Euser?q=a%2fb">6</a> <a href="/~user?q=a%2Fb">7</a>
+            "#),
+            _ => Response::html("<title>Page</title>"),
+        });
+
+        let results: Vec<_> = Crawler::new().crawl(server.url("/%7e")).unwrap().collect();
+        assert_eq!(*results[0].url, server.url("/~"));
+        let results: Vec<_> = Crawler::new().crawl(server.url("/")).unwrap().collect();
+        assert_eq!(server.requested_paths(), ["/~", "/", "/path%20with%20space", "/caf%C3%A9", "/~user?q=a%2Fb"]);
+        // The links themselves are kept as they were found.
+        let links: Vec<_> = results[0].page.as_ref().unwrap().links.iter().map(|link_url| link_url.path()).collect();
+        assert_eq!(links, ["/path%20with%20space", "/caf%C3%A9", "/caf%c3%a9", "/%7Euser", "/~user"]);
+        assert_eq!(Crawler::new().crawl_links(server.url("/")).unwrap().count(), 3);
+    }
+
+    #[test]
+    fn test_crawl_fragment_links() {
+        let server = TestServer::start(|req| {
+            let html = match &*req.path {
+                "/" => r##"<title>Home</title><a href="#top">Top</a> <a href="/other#section">Other</a>"##,
+                _ => r##"<title>Other</title><a href="/#top">Home</a>"##,
+            };
+            Response::html(html)
+        });
+
+        let crawler = Crawler::builder().keep_fragment_links(true).build().unwrap();
+        let pages: Vec<_> = crawler.crawl(server.url("/")).unwrap().collect();
+        let links = &pages[0].page.as_ref().unwrap().links;
+        assert!(links.contains(&server.url("/#top")));
+        assert!(links.contains(&server.url("/other#section")));
+        assert_eq!(server.requested_paths(), ["/", "/other"]);
+
+        let crawler = Crawler::new();
+        let pages: Vec<_> = crawler.crawl(server.url("/")).unwrap().collect();
+        assert_eq!(pages[0].page.as_ref().unwrap().links, [server.url("/other#section")]);
+    }
+
+    #[test]
+    fn test_crawl_resolve() {
+        let server = TestServer::start(|req| {
+            let html = match (req.header("Host"), &*req.path) {
+                (Some("example.com"), "/") => r#"<title>Home</title><a href="/about">About</a>"#,
+                (Some("example.com"), "/about") => r#"<title>About</title>"#,
+                _ => return Response::new(404),
+            };
+            Response::html(html)
+        });
+
+        let crawler = Crawler::builder().resolve("example.com", server.addr()).build().unwrap();
+        let urls: Vec<_> = crawler.crawl("http://example.com/").unwrap().map(|result| result.url.to_string()).collect();
+        assert_eq!(urls, ["http://example.com/", "http://example.com/about"]);
+        assert_eq!(server.requested_paths(), ["/", "/about"]);
+    }
+
+    #[test]
+    fn test_crawl_raw_html() {
+        const HTML: &str = "<html><head><title>Raw</title></head><body><p>Hello!</p></body></html>";
+        let server = TestServer::start(|_| Response::html(HTML));
+
+        let crawler = Crawler::builder().keep_raw_html(true).build().unwrap();
+        let results: Vec<_> = crawler.crawl(server.url("/")).unwrap().collect();
+        assert_eq!(results[0].raw_html.as_deref(), Some(HTML));
+
+        let crawler = Crawler::new();
+        let results: Vec<_> = crawler.crawl(server.url("/")).unwrap().collect();
+        assert_eq!(results[0].raw_html, None);
+    }
+
+    #[test]
+    fn test_crawl_with_documents() {
+        let server = TestServer::start(|req| match &*req.path {
+            "/" => Response::html(r#"<title>Home</title><p class="price">42</p><a href="/data.json">Data</a>"#),
+            "/data.json" => Response::new(200).header("Content-Type", "application/json").body("{}"),
+            _ => Response::not_found(),
+        });
+        let crawler = Crawler::builder().extra_content_type("application/json").build().unwrap();
+        let results: Vec<_> = crawler.crawl_with_documents(server.url("/")).unwrap().collect();
+        assert_eq!(results.len(), 2);
+        let prices: Vec<_> = results[0].1.as_ref().unwrap().find(Class("price")).map(|n| n.text()).collect();
+        assert_eq!(prices, ["42"]);
+        assert!(results[1].1.is_none());
+    }
+
+    #[test]
+    fn test_crawl_max_hosts() {
+        let servers: Vec<_> = (2..=5)
+            .map(|i| TestServer::start_on(&format!("127.0.0.{}", i), |_| Response::html("<title>Leaf</title>")))
+            .collect();
+        let links: String = servers.iter().map(|server| format!(r#"<a href="{}">Leaf</a>"#, server.url("/"))).collect();
+        let seed_server = TestServer::start(move |_| Response::html(format!("<title>Seed</title>{}", links)));
+
+        let crawler = Crawler::builder().max_hosts(3).allowed_ports(servers.iter().map(|server| server.addr().port())).build().unwrap();
+        let hosts: Vec<_> = crawler.crawl(seed_server.url("/")).unwrap().map(|result| result.url.host_str().unwrap().to_owned()).collect();
+        assert_eq!(hosts, ["127.0.0.1", "127.0.0.2", "127.0.0.3"]);
+        assert_eq!(servers[2].requested_paths().len(), 0);
+        assert_eq!(servers[3].requested_paths().len(), 0);
+    }
+
+    #[test]
+    fn test_crawl_adaptive_delay() {
+        let request_times = Arc::new(Mutex::new(vec![]));
+        let server_request_times = request_times.clone();
+        let server = TestServer::start(move |req| {
+            server_request_times.lock().unwrap().push(Instant::now());
+            match &*req.path {
+                "/" => Response::html((1..=6).map(|i| format!(r#"<a href="/{}">{}</a>"#, i, i)).collect::<String>() + "<title>Home</title>"),
+                "/1" | "/2" | "/3" => Response::new(503),
+                _ => Response::html("<title>Page</title>"),
+            }
+        });
+
+        let initial = Duration::from_millis(50);
+        let crawler = Crawler::builder().adaptive_delay(initial, Duration::from_secs(1)).build().unwrap();
+        assert_eq!(crawler.crawl(server.url("/")).unwrap().count(), 4);
+
+        let request_times = request_times.lock().unwrap();
+        let delays: Vec<_> = request_times.windows(2).map(|w| w[1] - w[0]).collect();
+        // The first failure introduces the delay, which doubles with each further failure and halves with each success.
+        let expected_delays = [0, 1, 2, 4, 2, 1].iter().map(|&n| initial * n);
+        // Allow for some jitter, since these are measured by the server.
+        for (delay, expected_delay) in delays.iter().zip(expected_delays) {
+            assert!(*delay + Duration::from_millis(10) >= expected_delay, "delay {:?} shorter than {:?}", delay, expected_delay);
+        }
+        assert!(delays[3] > delays[2] && delays[2] > delays[1]);
+        assert!(delays[3] > delays[4] && delays[4] > delays[5]);
+    }
+
+    #[test]
+    fn test_web_page_info_headings() {
+        let doc = Document::from(r#"
+            <title>Outline</title>
+            <h1> Fish &amp; Chips </h1>
+            <p>Introduction</p>
+            <h2>History</h2>
+            <section><h3>Origins</h3></section>
+            <h2>Preparation</h2>
+        "#);
+        let doc_info = get_web_page_info(&"http://example.com/".parse().unwrap(), &doc).unwrap();
+        assert_eq!(doc_info.headings, [
+            (1, "Fish & Chips".to_owned()),
+            (2, "History".to_owned()),
+            (3, "Origins".to_owned()),
+            (2, "Preparation".to_owned()),
+        ]);
+    }
+
+    #[test]
+    fn test_crawl_byte_size() {
+        const HOME: &str = r#"<title>Home</title><a href="/about">About</a>"#;
+        const ABOUT: &str = "<title>About</title><p>Caf\u{e9}</p>";
+        let server = TestServer::start(|req| match &*req.path {
+            "/" => Response::new(200).header("Content-Type", "Text/HTML; charset=utf-8").body(HOME),
+            _ => Response::html(ABOUT),
+        });
+
+        let results: Vec<_> = Crawler::new().crawl(server.url("/")).unwrap().collect();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].content_type, "text/html");
+        assert_eq!(results[0].byte_size, HOME.len());
+        assert_eq!(results[1].content_type, "text/html");
+        assert_eq!(results[1].byte_size, ABOUT.len());
+    }
+
+    #[test]
+    fn test_crawl_extra_content_types() {
+        const PDF: &[u8] = b"%PDF-1.4 <a href=\"/secret\">Not a link</a>";
+        let server = TestServer::start(|req| match &*req.path {
+            "/" => Response::html(r#"<title>Home</title><a href="/manual.pdf">Manual</a>"#),
+            "/manual.pdf" => Response::new(200).header("Content-Type", "application/pdf").body(PDF),
+            _ => Response::html("<title>Secret</title>"),
+        });
+
+        let crawler = Crawler::builder().extra_content_type("application/pdf").build().unwrap();
+        let results: Vec<_> = crawler.crawl(server.url("/")).unwrap().collect();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].content_type, "text/html");
+        assert!(results[0].page.is_some());
+        assert_eq!(*results[1].url, server.url("/manual.pdf"));
+        assert_eq!(results[1].content_type, "application/pdf");
+        assert_eq!(results[1].byte_size, PDF.len());
+        assert!(results[1].page.is_none());
+        assert_eq!(server.requested_paths(), ["/", "/manual.pdf"]);
+
+        // Without the extra content type, the PDF is skipped altogether.
+        let results: Vec<_> = Crawler::new().crawl(server.url("/")).unwrap().collect();
+        assert_eq!(results.len(), 1);
+    }
+
+    #[test]
+    fn test_crawler_into_iter() {
+        let server = TestServer::start(|req| {
+            Response::html(format!(r#"<title>Page {0}</title><a href="{0}a">A</a> <a href="{0}b">B</a>"#, req.path))
+        });
+
+        let mut titles = vec![];
+        for result in Crawler::builder().max_pages(4).build().unwrap().into_iter(server.url("/")).unwrap() {
+            titles.push(result.page.unwrap().title);
+        }
+        assert_eq!(titles, ["Page /", "Page /a", "Page /b", "Page /aa"]);
+        assert_eq!(server.requested_paths().len(), 4);
+    }
+
+    #[test]
+    fn test_crawl_logging() {
+        struct TestLogger(Mutex<Vec<(log::Level, String)>>);
+
+        impl log::Log for TestLogger {
+            fn enabled(&self, _: &log::Metadata) -> bool {
+                true
+            }
+
+            fn log(&self, record: &log::Record) {
+                self.0.lock().unwrap().push((record.level(), record.args().to_string()));
+            }
+
+            fn flush(&self) {}
+        }
+
+        static LOGGER: TestLogger = TestLogger(Mutex::new(vec![]));
+        log::set_logger(&LOGGER).unwrap();
+        log::set_max_level(log::LevelFilter::Debug);
+
+        let server = TestServer::with_pages(&[("/", r#"<title>Home</title><a href="/missing">Missing</a>"#)]);
+        assert_eq!(Crawler::new().crawl(server.url("/")).unwrap().count(), 1);
+
+        let records = LOGGER.0.lock().unwrap();
+        let has_record = |level, message: &str| records.iter().any(|(l, m)| *l == level && m.contains(message));
+        assert!(has_record(log::Level::Debug, &format!("fetched {} with status 200 OK", server.url("/"))));
+        assert!(has_record(log::Level::Warn, &format!("skipping {}: bad HTTP status: 404 Not Found", server.url("/missing"))));
+        assert!(has_record(log::Level::Info, &format!("finished crawl at {} after 1 pages", server.url("/"))));
+    }
+
+    #[test]
+    fn test_crawl_max_pages_per_host() {
+        let other_server = TestServer::start_on("127.0.0.2", |_| Response::html("<title>Other</title>"));
+        let other_url = other_server.url("/");
+        let server = TestServer::start(move |req| {
+            let links: String = (0..10).map(|i| format!(r#"<a href="{}{}">{}</a>"#, req.path, i, i)).collect();
+            Response::html(format!(r#"<title>Page</title>{}<a href="{}">Other</a>"#, links, other_url))
+        });
+
+        let crawler = Crawler::builder().max_pages_per_host(3).allowed_ports(vec![other_server.addr().port()]).build().unwrap();
+        let hosts: Vec<_> = crawler.crawl(server.url("/")).unwrap().map(|result| result.url.host_str().unwrap().to_owned()).collect();
+        assert_eq!(hosts, ["127.0.0.1", "127.0.0.1", "127.0.0.1", "127.0.0.2"]);
+        assert_eq!(server.requested_paths().len(), 3);
+    }
+
+    #[test]
+    fn test_web_page_info_resource_hints() {
+        let doc = Document::from(r#"
+            <head>
+                <title>Hints</title>
+                <link rel="stylesheet" href="/style.css">
+                <link rel="preload" href="/fonts/body.woff2" as="font" crossorigin>
+                <link rel="PREFETCH" href="next.html">
+            </head>
+        "#);
+        let doc_info = get_web_page_info(&"https://example.com/docs/".parse().unwrap(), &doc).unwrap();
+        assert_eq!(doc_info.resource_hints, [
+            ResourceHint {
+                url: "https://example.com/fonts/body.woff2".parse().unwrap(),
+                rel: "preload".to_owned(),
+                as_type: Some("font".to_owned()),
+            },
+            ResourceHint {
+                url: "https://example.com/docs/next.html".parse().unwrap(),
+                rel: "prefetch".to_owned(),
+                as_type: None,
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_crawl_custom_frontier() {
+        #[derive(Default)]
+        struct LifoFrontier(Vec<Arc<Url>>);
+
+        impl Frontier for LifoFrontier {
+            fn push(&mut self, url: Arc<Url>) {
+                self.0.push(url);
+            }
+
+            fn pop(&mut self) -> Option<Arc<Url>> {
+                self.0.pop()
+            }
+
+            fn len(&self) -> usize {
+                self.0.len()
+            }
+        }
+
+        let server = TestServer::with_pages(&[
+            ("/", r#"<title>Root</title><a href="/a">A</a> <a href="/b">B</a>"#),
+            ("/a", r#"<title>A</title><a href="/a/1">A1</a>"#),
+            ("/a/1", r#"<title>A1</title>"#),
+            ("/b", r#"<title>B</title><a href="/b/1">B1</a>"#),
+            ("/b/1", r#"<title>B1</title>"#),
+        ]);
+
+        let crawler = Crawler::builder().frontier(|| Box::new(LifoFrontier::default())).build().unwrap();
+        let paths: Vec<_> = crawler.crawl(server.url("/")).unwrap().map(|result| result.url.path().to_owned()).collect();
+        assert_eq!(paths, ["/", "/b", "/b/1", "/a", "/a/1"]);
+    }
+
+    #[test]
+    fn test_crawl_custom_visited_set() {
+        #[derive(Clone, Debug, PartialEq)]
+        enum Op {
+            Insert(String),
+            Contains(String),
+        }
+
+        struct MockVisitedSet(HashVisitedSet, Arc<Mutex<Vec<Op>>>);
+
+        impl VisitedSet for MockVisitedSet {
+            fn insert(&mut self, url: &Arc<Url>) {
+                self.1.lock().unwrap().push(Op::Insert(url.path().to_owned()));
+                self.0.insert(url);
+            }
+
+            fn contains(&self, url: &Url) -> bool {
+                self.1.lock().unwrap().push(Op::Contains(url.path().to_owned()));
+                self.0.contains(url)
+            }
+        }
+
+        let server = TestServer::with_pages(&[
+            ("/", r#"<title>Root</title><a href="/a">A</a> <a href="/b">B</a>"#),
+            ("/a", r#"<title>A</title><a href="/">Root</a>"#),
+            ("/b", r#"<title>B</title><a href="/a">A</a>"#),
+        ]);
+
+        let ops = Arc::new(Mutex::new(vec![]));
+        let visited_ops = ops.clone();
+        let crawler = Crawler::builder()
+            .visited_set(move || Box::new(MockVisitedSet(HashVisitedSet::new(), visited_ops.clone())))
+            .build()
+            .unwrap();
+        assert_eq!(crawler.crawl(server.url("/")).unwrap().count(), 3);
+
+        let insert = |path: &str| Op::Insert(path.to_owned());
+        let contains = |path: &str| Op::Contains(path.to_owned());
+        assert_eq!(*ops.lock().unwrap(), [
+            insert("/"),
+            contains("/a"),
+            insert("/a"),
+            contains("/b"),
+            insert("/b"),
+            contains("/"),
+            contains("/a"),
+        ]);
+    }
+
+    #[test]
+    fn test_crawl_invalid_seed() {
+        assert!(is_match!(Crawler::new().crawl("not a URL"), Err(CrawlError::InvalidSeed(_))));
+        assert!(is_match!(Crawler::new().crawl("mailto:someone@example.com"), Err(CrawlError::InvalidSeed(_))));
+
+        // A valid seed whose site is empty still crawls successfully, albeit producing nothing.
+        let server = TestServer::start(|_| Response::not_found());
+        assert_eq!(Crawler::new().crawl(server.url("/")).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_crawl_redirects() {
+        let server = TestServer::start(|req| match &*req.path {
+            "/start" => Response::new(301).header("Location", "/hop1"),
+            "/hop1" => Response::new(302).header("Location", "hop2"),
+            "/hop2" => Response::new(307).header("Location", "/final"),
+            "/final" => Response::html(r#"<title>Final</title><a href="/start">Start</a> <a href="/hop2">Hop</a> <a href="/other">Other</a>"#),
+            _ => Response::html("<title>Other</title>"),
+        });
+
+        let crawler = Crawler::builder().max_pages(1).build().unwrap();
+        let paths: Vec<_> = crawler.crawl(server.url("/start")).unwrap().map(|result| result.url.path().to_owned()).collect();
+        assert_eq!(paths, ["/final"]);
+
+        // The intermediate URLs are never revisited, and don't count towards the maximum number of pages.
+        let crawler = Crawler::builder().max_pages(2).build().unwrap();
+        let paths: Vec<_> = crawler.crawl(server.url("/start")).unwrap().map(|result| result.url.path().to_owned()).collect();
+        assert_eq!(paths, ["/final", "/other"]);
+        assert_eq!(server.requested_paths()[4..], ["/start", "/hop1", "/hop2", "/final", "/other"]);
+    }
+
+    #[test]
+    fn test_crawl_interleave_hosts() {
+        let other_server = TestServer::start_on("127.0.0.2", |_| Response::html("<title>Other</title>"));
+        let other_links: String = (1..=3).map(|i| format!(r#"<a href="{}">Other</a>"#, other_server.url(&format!("/{}", i)))).collect();
+        let server = TestServer::start(move |req| match &*req.path {
+            "/" => Response::html(format!(r#"<title>Home</title><a href="/1">1</a> <a href="/2">2</a> <a href="/3">3</a>{}"#, other_links)),
+            _ => Response::html("<title>Page</title>"),
+        });
+
+        let other_ports = vec![other_server.addr().port()];
+        let crawler = Crawler::builder().interleave_hosts(true).allowed_ports(other_ports.clone()).build().unwrap();
+        let hosts: Vec<_> = crawler.crawl(server.url("/")).unwrap().map(|result| result.url.host_str().unwrap().to_owned()).collect();
+        assert_eq!(hosts, ["127.0.0.1", "127.0.0.1", "127.0.0.2", "127.0.0.1", "127.0.0.2", "127.0.0.1", "127.0.0.2"]);
+
+        // With a request delay, the host that has been waiting longest goes first.
+        let crawler = Crawler::builder()
+            .interleave_hosts(true)
+            .request_delay(Duration::from_millis(100))
+            .allowed_ports(other_ports)
+            .build().unwrap();
+        let hosts: Vec<_> = crawler.crawl(server.url("/")).unwrap().map(|result| result.url.host_str().unwrap().to_owned()).collect();
+        assert_eq!(hosts, ["127.0.0.1", "127.0.0.2", "127.0.0.1", "127.0.0.2", "127.0.0.1", "127.0.0.2", "127.0.0.1"]);
+    }
+
+    #[test]
+    fn test_crawl_sorted() {
+        let server = TestServer::with_pages(&[
+            ("/", r#"<title>Home</title><a href="/a">A</a> <a href="/b">B</a>"#),
+            ("/a", r#"<title>A</title><a href="/c">C</a> <a href="/">Home</a>"#),
+            ("/b", r#"<title>B</title><a href="/a">A</a> <a href="/c">C</a>"#),
+            ("/c", r#"<title>C</title><a href="/d">D</a>"#),
+            ("/d", "<title>D</title>"),
+        ]);
+
+        let results = Crawler::new().crawl_sorted(server.url("/")).unwrap();
+        let paths: Vec<_> = results.iter().map(|result| result.url.path()).collect();
+        assert_eq!(paths, ["/", "/b", "/a", "/c", "/d"]);
+        for (i, result) in results.iter().enumerate() {
+            for link_url in &result.page.as_ref().unwrap().links {
+                let j = results.iter().position(|result| *result.url == *link_url).unwrap();
+                assert!(i < j || link_url.path() == "/", "{} appears after {}", result.url, link_url);
+            }
+        }
+    }
+
+    #[test]
+    fn test_crawl_login() {
+        let server = TestServer::start(|req| match (&*req.method, &*req.path) {
+            ("POST", "/login") if req.body == b"user=alice&password=secret" => Response::new(303)
+                .header("Set-Cookie", "session=1234; Path=/")
+                .header("Location", "/"),
+            ("POST", "/login") => Response::new(401),
+            _ if req.header("Cookie") != Some("session=1234") => Response::new(403),
+            (_, "/") => Response::html(r#"<title>Home</title><a href="/private">Private</a>"#),
+            _ => Response::html("<title>Private</title>"),
+        });
+        let login = |password: &str| LoginConfig {
+            url: server.url("/login"),
+            form_fields: vec![("user".to_owned(), "alice".to_owned()), ("password".to_owned(), password.to_owned())],
+        };
+
+        assert_eq!(Crawler::new().crawl(server.url("/")).unwrap().count(), 0);
+
+        let crawler = Crawler::builder().login(login("secret")).build().unwrap();
+        let paths: Vec<_> = crawler.crawl(server.url("/")).unwrap().map(|result| result.url.path().to_owned()).collect();
+        assert_eq!(paths, ["/", "/private"]);
+
+        let crawler = Crawler::builder().login(login("wrong")).build().unwrap();
+        assert!(is_match!(crawler.crawl(server.url("/")), Err(CrawlError::LoginFailed(_))));
+    }
+
+    #[test]
+    fn test_crawl_spider_traps() {
+        // Every page links to a page one level deeper, both by repeating a segment and by adding a new one.
+        let server = TestServer::start(|req| {
+            let path = req.path.trim_end_matches('/');
+            let depth = path.matches('/').count();
+            Response::html(format!(r#"<title>Trap</title><a href="{0}/a">Again</a> <a href="{0}/{1}">Deeper</a>"#, path, depth))
+        });
+
+        let crawler = Crawler::builder().max_path_segment_repeats(2).max_path_depth(3).max_pages(1000).build().unwrap();
+        let mut paths: Vec<_> = crawler.crawl(server.url("/")).unwrap().map(|result| result.url.path().to_owned()).collect();
+        paths.sort();
+        assert_eq!(paths, [
+            "/", "/0", "/0/1", "/0/1/2", "/0/1/a", "/0/a", "/0/a/2", "/0/a/a",
+            "/a", "/a/1", "/a/1/2", "/a/1/a", "/a/a", "/a/a/2",
+        ]);
+    }
+
+    #[test]
+    fn test_crawl_circuit_breaker() {
+        let down_server = TestServer::start_on("127.0.0.2", |_| Response::new(503));
+        let down_links: String = (1..=5).map(|i| format!(r#"<a href="{}">Down</a>"#, down_server.url(&format!("/{}", i)))).collect();
+        let server = TestServer::start(move |req| match &*req.path {
+            "/" => Response::html(format!(r#"<title>Home</title>{}<a href="/about">About</a>"#, down_links)),
+            _ => Response::html("<title>About</title>"),
+        });
+
+        let crawler = Crawler::builder()
+            .circuit_breaker(2, Duration::from_secs(60))
+            .allowed_ports(vec![down_server.addr().port()])
+            .build().unwrap();
+        let paths: Vec<_> = crawler.crawl(server.url("/")).unwrap().map(|result| result.url.path().to_owned()).collect();
+        assert_eq!(paths, ["/", "/about"]);
+        assert_eq!(down_server.requested_paths(), ["/1", "/2"]);
+    }
+
+    #[test]
+    fn test_crawl_client() {
+        let server = TestServer::with_pages(&[
+            ("/", r#"<title>Home</title><a href="/about">About</a>"#),
+            ("/about", "<title>About</title>"),
+        ]);
+
+        let mut headers = header::HeaderMap::new();
+        headers.insert(header::USER_AGENT, HeaderValue::from_static("distinctive-crawler/1.0"));
+        let client = Client::builder().default_headers(headers).build().unwrap();
+        let crawler = Crawler::builder().client(client).build().unwrap();
+        assert_eq!(crawler.crawl(server.url("/")).unwrap().count(), 2);
+
+        let user_agents: Vec<_> = server.requests().iter().map(|req| req.header("User-Agent").map(str::to_owned)).collect();
+        assert_eq!(user_agents, [Some("distinctive-crawler/1.0".to_owned()), Some("distinctive-crawler/1.0".to_owned())]);
+    }
+
+    #[test]
+    fn test_crawl_content_location() {
+        let server = TestServer::start(|req| match &*req.path {
+            "/" => Response::html(r#"<title>Home</title><a href="/a">A</a> <a href="/b">B</a> <a href="/c">C</a>"#),
+            "/a" | "/b" => Response::html("<title>Article</title>").header("Content-Location", "/article"),
+            _ => Response::html("<title>C</title>").header("Content-Location", "/c"),
+        });
+
+        let paths: Vec<_> = Crawler::new().crawl(server.url("/")).unwrap().map(|result| result.url.path().to_owned()).collect();
+        assert_eq!(paths, ["/", "/a", "/c"]);
+    }
+
+    #[test]
+    fn test_crawl_cancel() {
+        // An endless chain of pages.
+        let server = TestServer::start(|req| {
+            let page: usize = req.path[1..].parse().unwrap_or(0);
+            Response::html(format!(r#"<title>Page {0}</title><a href="/{1}">Page {1}</a>"#, page, page + 1))
+        });
+
+        let crawler = Crawler::new();
+        let cancel_handle = crawler.cancel_handle();
+        let mut results = crawler.crawl(server.url("/")).unwrap();
+        assert!(results.next().is_some());
+        assert!(results.next().is_some());
+        cancel_handle.cancel();
+        assert!(results.next().is_none());
+        assert_eq!(server.requested_paths(), ["/", "/1"]);
+
+        // Cancelling also interrupts a crawl that is waiting to make its next request.
+        let crawler = Crawler::builder().request_delay(Duration::from_millis(200)).build().unwrap();
+        let cancel_handle = crawler.cancel_handle();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(300));
+            cancel_handle.cancel();
+        });
+        let start = Instant::now();
+        assert_eq!(crawler.crawl(server.url("/")).unwrap().count(), 2);
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        // Crawls started after cancelling (of the crawler or a clone of it) aren't cancelled.
+        let crawler = Crawler::builder().max_pages(3).build().unwrap();
+        crawler.cancel_handle().cancel();
+        assert_eq!(crawler.crawl(server.url("/")).unwrap().count(), 3);
+        assert_eq!(crawler.clone().crawl(server.url("/")).unwrap().count(), 3);
+    }
+
+    #[test]
+    fn test_crawl_external_links() {
+        let other_server = TestServer::start_on("127.0.0.2", |_| Response::html("<title>Other</title>"));
+        let other_url = other_server.url("/");
+        let other_url_2 = other_server.url("/2");
+        let server = TestServer::start(move |req| match &*req.path {
+            "/" => Response::html(format!(r##"<title>Home</title><a href="/about">About</a> <a href="{0}">Other</a> <a href="{0}#top">Other again</a>"##, other_url)),
+            _ => Response::html(format!(r#"<title>About</title><a href="/">Home</a> <a href="{}">Other</a> <a href="{}">Other 2</a>"#, other_url, other_url_2)),
+        });
+
+        let external_links = Crawler::new().crawl_external_links(server.url("/")).unwrap();
+        let mut expected = HashMap::new();
+        expected.insert(other_server.url("/"), 2);
+        expected.insert(other_server.url("/2"), 1);
+        assert_eq!(external_links, expected);
+    }
+
+    #[test]
+    fn test_crawler_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Crawler>();
+        assert_send_sync::<CrawlerBuilder>();
+        assert_send_sync::<CancelHandle>();
+
+        let server = TestServer::with_pages(&[("/", "<title>Home</title>")]);
+        let crawler = Arc::new(Crawler::new());
+        let threads: Vec<_> = (0..2).map(|_| {
+            let crawler = crawler.clone();
+            let url = server.url("/");
+            thread::spawn(move || crawler.crawl(url).unwrap().count())
+        }).collect();
+        for thread in threads {
+            assert_eq!(thread.join().unwrap(), 1);
+        }
+    }
+
+    #[test]
+    fn test_crawl_http_version() {
+        let server = TestServer::with_pages(&[
+            ("/", r#"<title>Home</title><a href="/about">About</a>"#),
+            ("/about", "<title>About</title>"),
+        ]);
+
+        let versions: Vec<_> = Crawler::new().crawl(server.url("/")).unwrap().map(|result| result.http_version).collect();
+        assert_eq!(versions, [Version::HTTP_11, Version::HTTP_11]);
+    }
+
+    #[test]
+    fn test_crawl_timeouts() {
+        let black_hole = BlackHole::start();
+        let crawler = Crawler::builder().connect_timeout(Duration::from_millis(100)).build().unwrap();
+        let start = Instant::now();
+        let fetched = fetch_web_page(crawler.client.get(&*format!("http://{}/", black_hole.addr())), &FetchOptions::default());
+        assert!(is_match!(fetched, Err(FetchWebPageError::ConnectTimeout(_))));
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        // A slow server is a different matter.
+        let server = TestServer::start(|_| {
+            thread::sleep(Duration::from_millis(500));
+            Response::html("<title>Slow</title>")
+        });
+        let crawler = Crawler::builder().connect_timeout(Duration::from_millis(100)).build().unwrap();
+        assert!(fetch_web_page(crawler.client.get(server.url("/")), &FetchOptions::default()).is_ok());
+        let crawler = Crawler::builder().timeout(Duration::from_millis(100)).build().unwrap();
+        let fetched = fetch_web_page(crawler.client.get(server.url("/")), &FetchOptions::default());
+        assert!(is_match!(fetched, Err(FetchWebPageError::HttpError(ref err)) if err.is_timeout()));
+    }
+
+    #[test]
+    fn test_fetch_web_page_connection_errors() {
+        let client = Client::new();
+        let fetched = fetch_web_page(client.get("http://nonexistent.invalid/"), &FetchOptions::default());
+        assert!(is_match!(fetched, Err(FetchWebPageError::DnsError(_))));
+
+        let refused_addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let fetched = fetch_web_page(client.get(&*format!("http://{}/", refused_addr)), &FetchOptions::default());
+        assert!(is_match!(fetched, Err(FetchWebPageError::ConnectError(_))));
+
+        // A server that doesn't speak TLS at all.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let _ = stream.and_then(|mut stream| stream.write_all(b"HTTP/1.1 400 Bad Request\r\n\r\n"));
+            }
+        });
+        let fetched = fetch_web_page(client.get(&*format!("https://{}/", addr)), &FetchOptions::default());
+        assert!(is_match!(fetched, Err(FetchWebPageError::TlsError(_))));
+    }
+
+    #[test]
+    fn test_crawl_links() {
+        let server = TestServer::with_pages(&[
+            ("/", r#"<title>Home</title><a href="/a">A</a> <a href="/b">B</a>"#),
+            ("/a", r#"<title>A</title><a href="/">Home</a> <a href="/b">B</a> <a href="/missing">Missing</a>"#),
+            ("/b", r#"<title>B</title><a href="/a">A</a> <a href="http://example.com/">Example</a>"#),
+        ]);
+
+        let crawler = Crawler::builder().max_hosts(1).build().unwrap();
+        let links: Vec<_> = crawler.crawl_links(server.url("/")).unwrap().collect();
+        let expected: HashSet<_> = crawler.crawl(server.url("/")).unwrap()
+            .flat_map(|result| result.page.unwrap().links)
+            .collect();
+        assert_eq!(links.len(), expected.len());
+        assert_eq!(links.into_iter().collect::<HashSet<_>>(), expected);
+    }
+
+    #[test]
+    fn test_crawl_accepted_statuses() {
+        let server = TestServer::start(|req| match &*req.path {
+            "/" => Response::html(r#"<title>Home</title><a href="/missing">Missing</a> <a href="/empty">Empty</a>"#),
+            "/empty" => Response::new(204).header("Content-Type", "text/html"),
+            _ => Response::new(404).header("Content-Type", "text/html").body(r#"<title>Not Found</title><a href="/">Home</a>"#),
+        });
+
+        let paths: Vec<_> = Crawler::new().crawl(server.url("/")).unwrap().map(|result| result.url.path().to_owned()).collect();
+        assert_eq!(paths, ["/"]);
+
+        let crawler = Crawler::builder().accepted_statuses(vec![StatusCode::OK, StatusCode::NOT_FOUND]).build().unwrap();
+        let results: Vec<_> = crawler.crawl(server.url("/")).unwrap().collect();
+        assert_eq!(results.len(), 2);
+        assert_eq!(*results[1].url, server.url("/missing"));
+        assert_eq!(results[1].page.as_ref().unwrap().title, "Not Found");
+    }
+
+    #[test]
+    fn test_crawl_frontier_len() {
+        let server = TestServer::with_pages(&[
+            ("/", r#"<title>Home</title><a href="/a">A</a> <a href="/b">B</a> <a href="/c">C</a>"#),
+            ("/a", r#"<title>A</title><a href="/d">D</a>"#),
+            ("/b", "<title>B</title>"),
+            ("/c", "<title>C</title>"),
+            ("/d", "<title>D</title>"),
+        ]);
+
+        let mut results = Crawler::new().crawl(server.url("/")).unwrap();
+        let frontier_len = results.frontier_len_handle();
+        assert_eq!(results.frontier_len(), 1);
+        let mut frontier_lens = vec![];
+        while results.next().is_some() {
+            frontier_lens.push(frontier_len.get());
+        }
+        assert_eq!(frontier_lens, [3, 3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn test_write_sitemap() {
+        let other_server = TestServer::start_on("127.0.0.2", |_| Response::html("<title>Other</title>"));
+        let other_url = other_server.url("/");
+        let server = TestServer::start(move |req| match &*req.path {
+            "/" => Response::html(format!(r#"<title>Home</title><a href="/a?x=1&amp;y=2">A</a> <a href="{}">Other</a>"#, other_url))
+                .header("Last-Modified", "Wed, 21 Oct 2015 07:28:00 GMT"),
+            _ => Response::html("<title>A</title>"),
+        });
+
+        let crawler = Crawler::builder().allowed_ports(vec![other_server.addr().port()]).build().unwrap();
+        let results: Vec<_> = crawler.crawl(server.url("/")).unwrap().collect();
+        assert_eq!(results.len(), 3);
+        let mut sitemap = vec![];
+        write_sitemap(&results, &mut sitemap).unwrap();
+        assert_eq!(String::from_utf8(sitemap).unwrap(), format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <url>
+    <loc>{}</loc>
+    <lastmod>2015-10-21T07:28:00Z</lastmod>
+  </url>
+  <url>
+    <loc>{}</loc>
+  </url>
+</urlset>
+"#, server.url("/"), server.url("/a?x=1&y=2").as_str().replace('&', "&amp;")));
+    }
+
+    #[test]
+    fn test_crawl_deterministic() {
+        let other_server = TestServer::start_on("127.0.0.2", |req| match &*req.path {
+            "/" => Response::html(r#"<title>Other</title><a href="/x">X</a> <a href="/y">Y</a>"#),
+            _ => Response::html("<title>Other page</title>"),
+        });
+        let other_url = other_server.url("/");
+        let server = TestServer::start(move |req| match &*req.path {
+            "/" => Response::html(format!(r#"<title>Home</title><a href="/a">A</a> <a href="{}">Other</a> <a href="/b">B</a>"#, other_url)),
+            "/a" => Response::html(r#"<title>A</title><a href="/c">C</a>"#),
+            _ => Response::html("<title>Page</title>"),
+        });
+
+        let crawler = Crawler::builder()
+            .interleave_hosts(true)
+            .request_delay(Duration::from_millis(20))
+            .deterministic(true)
+            .allowed_ports(vec![other_server.addr().port()])
+            .build().unwrap();
+        let crawl = || crawler.crawl(server.url("/")).unwrap().map(|result| unshare_url(result.url)).collect::<Vec<_>>();
+        let urls = crawl();
+        assert_eq!(urls, [
+            server.url("/"),
+            server.url("/a"),
+            other_server.url("/"),
+            server.url("/b"),
+            other_server.url("/x"),
+            server.url("/c"),
+            other_server.url("/y"),
+        ]);
+        assert_eq!(crawl(), urls);
+    }
+
+    #[test]
+    fn test_crawl_fail_on_seed_error() {
+        let unreachable_addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let unreachable_url = format!("http://{}/", unreachable_addr);
+        let server = TestServer::start(|req| match &*req.path {
+            "/" => Response::html(r#"<title>Home</title><a href="/broken">Broken</a>"#),
+            _ => Response::new(500),
+        });
+
+        assert_eq!(Crawler::new().crawl(&*unreachable_url).unwrap().count(), 0);
+
+        let crawler = Crawler::builder().fail_on_seed_error(true).build().unwrap();
+        assert!(is_match!(crawler.crawl(&*unreachable_url), Err(CrawlError::SeedFailed(FetchWebPageError::ConnectError(_)))));
+        assert!(is_match!(
+            crawler.crawl(server.url("/broken")),
+            Err(CrawlError::SeedFailed(FetchWebPageError::BadHttpStatus(StatusCode::INTERNAL_SERVER_ERROR)))
+        ));
+
+        // Other pages may still fail, and the seed is only fetched once.
+        let paths: Vec<_> = crawler.crawl(server.url("/")).unwrap().map(|result| result.url.path().to_owned()).collect();
+        assert_eq!(paths, ["/"]);
+        assert_eq!(server.requested_paths(), ["/broken", "/", "/broken"]);
+    }
+
+    #[test]
+    fn test_crawl_timing() {
+        let server = TestServer::start(|req| match &*req.path {
+            "/" => Response::html(r#"<title>Home</title><a href="/slow">Slow</a>"#),
+            _ => {
+                thread::sleep(Duration::from_millis(50));
+                Response::html("<title>Slow</title>")
+            }
+        });
+
+        assert!(Crawler::new().crawl(server.url("/")).unwrap().all(|result| result.timing.is_none()));
+
+        let crawler = Crawler::builder().timing(true).build().unwrap();
+        let timings: Vec<_> = crawler.crawl(server.url("/")).unwrap().map(|result| result.timing.unwrap()).collect();
+        assert_eq!(timings.len(), 2);
+        for timing in &timings {
+            assert!(timing.dns <= timing.connect);
+            assert!(timing.connect <= timing.ttfb);
+            assert!(timing.ttfb <= timing.total);
+        }
+        assert!(timings[1].ttfb >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn test_crawl_web_page() {
+        let pages = crawl_web_page("http://rust-lang.org/");
+
+        let initial_pages: Vec<_> = pages.take(10).map(|(url, page)| (url.to_string(), page.title)).collect();
+        assert_eq!(&initial_pages[0],
+            &("http://rust-lang.org/".to_owned(), "Rust Programming Language".to_owned())
+        );
+        assert!(initial_pages.contains(
+            &("https://blog.rust-lang.org/".to_owned(), "The Rust Programming Language Blog".to_owned())
+        ));
+        assert!(initial_pages.contains(
+            &("https://github.com/rust-lang/rust/blob/master/CONTRIBUTING.md".to_owned(), "rust/CONTRIBUTING.md at master · rust-lang/rust · GitHub".to_owned())
+        ));
+
+        // TODO: test other websites, cyclic cases, pages with dead/invalid links, etc.
+    }
+
+    #[test]
+    fn test_crawl_path_prefix() {
+        let server = TestServer::start(|req| {
+            let html = match &*req.path {
+                "/" => r#"<title>Home</title><a href="/docs/v2/">Docs</a>"#,
+                "/docs/v2/" => r#"<title>Docs</title><a href="/docs/v2/intro">Intro</a> <a href="/docs/v1/">Old docs</a> <a href="/blog/">Blog</a>"#,
+                "/docs/v2/intro" => "<title>Intro</title>",
+                _ => r#"<title>Outside</title>"#,
+            };
+            Response::html(html.replace("href=\"", &format!("href=\"http://{}", req.header("Host").unwrap())))
+        });
+
+        let crawler = Crawler::builder().path_prefix("/docs/v2/").build().unwrap();
+        let urls: Vec<_> = crawler.crawl(server.url("/docs/v2/")).unwrap().map(|result| result.url.path().to_owned()).collect();
+        assert_eq!(urls, ["/docs/v2/", "/docs/v2/intro"]);
+        assert_eq!(server.requested_paths(), ["/docs/v2/", "/docs/v2/intro"]);
+    }
+
+    #[test]
+    fn test_crawl_mirror_to() {
+        let server = TestServer::start(|req| match &*req.path {
+            "/" => Response::html(r#"<title>Home</title><a href="/docs/">Docs</a> <a href="/docs/a%20b?x=1&y=2">Page</a> <a href="/logo.png">Logo</a>"#),
+            "/docs/" => Response::html("<title>Docs</title>"),
+            "/docs/a%20b?x=1&y=2" => Response::html("<title>Page</title>"),
+            "/logo.png" => Response::new(200).header("Content-Type", "image/png").body(&b"\x89PNG"[..]),
+            _ => Response::not_found(),
+        });
+        let dir = std::env::temp_dir().join(format!("web-crawler-test-mirror-{}", std::process::id()));
+        let crawler = Crawler::builder().mirror_to(&dir).extra_content_type("image/*").build().unwrap();
+        assert_eq!(crawler.crawl(server.url("/")).unwrap().count(), 4);
+
+        let host_dir = dir.join(server.addr().to_string().replace(':', "_"));
+        assert_eq!(std::fs::read_to_string(host_dir.join("index.html")).unwrap(),
+            r#"<title>Home</title><a href="/docs/">Docs</a> <a href="/docs/a%20b?x=1&y=2">Page</a> <a href="/logo.png">Logo</a>"#);
+        assert_eq!(std::fs::read_to_string(host_dir.join("docs/index.html")).unwrap(), "<title>Docs</title>");
+        assert_eq!(std::fs::read_to_string(host_dir.join("docs/a%20b_x=1&y=2")).unwrap(), "<title>Page</title>");
+        assert_eq!(std::fs::read(host_dir.join("logo.png")).unwrap(), b"\x89PNG");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_crawl_respect_link_types() {
+        let server = TestServer::with_pages(&[
+            ("/", r#"<title>Home</title>
+                <a href="/report.pdf" type="application/pdf">Report</a>
+                <a href="/about" type="text/html; charset=utf-8">About</a>
+                <a href="/contact">Contact</a>"#),
+            ("/about", "<title>About</title>"),
+            ("/contact", "<title>Contact</title>"),
+        ]);
+        let crawler = Crawler::builder().respect_link_types(true).build().unwrap();
+        let results: Vec<_> = crawler.crawl(server.url("/")).unwrap().collect();
+        assert_eq!(results[0].page.as_ref().unwrap().link_types, [
+            (server.url("/report.pdf"), "application/pdf".to_owned()),
+            (server.url("/about"), "text/html".to_owned()),
+        ]);
+        let paths: Vec<_> = results.iter().map(|result| result.url.path()).collect();
+        assert_eq!(paths, ["/", "/about", "/contact"]);
+        assert_eq!(server.requested_paths(), ["/", "/about", "/contact"]);
+    }
+
+    #[test]
+    fn test_crawl_sort_query_params() {
+        let server = TestServer::with_pages(&[
+            ("/", r#"<title>Home</title> <a href="/search?b=2&a=1">B then A</a> <a href="/search?a=1&b=2">A then B</a>"#),
+            ("/search?a=1&b=2", "<title>Search</title>"),
+        ]);
+        let crawler = Crawler::builder().sort_query_params(true).build().unwrap();
+        let urls: Vec<_> = crawler.crawl(server.url("/")).unwrap().map(|result| unshare_url(result.url)).collect();
+        assert_eq!(urls, [server.url("/"), server.url("/search?a=1&b=2")]);
+        assert_eq!(server.requested_paths(), ["/", "/search?a=1&b=2"]);
+
+        let mut url = server.url("/search?b=2&a=1&a=0&c");
+        sort_query_params(&mut url);
+        assert_eq!(url.query(), Some("a=0&a=1&b=2&c"));
+    }
+
+    #[test]
+    fn test_link_in_degrees_and_page_ranks() {
+        let server = TestServer::with_pages(&[
+            ("/", r#"<title>Home</title> <a href="/a">A</a> <a href="/b">B</a> <a href="/">Home</a>"#),
+            ("/a", r#"<title>A</title> <a href="/b">B</a> <a href="/b#top">B</a> <a href="/missing">Missing</a>"#),
+            ("/b", r#"<title>B</title> <a href="/">Home</a>"#),
+        ]);
+        let results: Vec<_> = Crawler::new().crawl(server.url("/")).unwrap().collect();
+
+        let in_degrees = link_in_degrees(&results);
+        assert_eq!(in_degrees.len(), 4);
+        assert_eq!(in_degrees[&server.url("/")], 1);
+        assert_eq!(in_degrees[&server.url("/a")], 1);
+        assert_eq!(in_degrees[&server.url("/b")], 2);
+        assert_eq!(in_degrees[&server.url("/missing")], 1);
+
+        let ranks = page_ranks(&results);
+        assert_eq!(ranks.len(), 3);
+        assert!((ranks.values().sum::<f64>() - 1.0).abs() < 1e-6);
+        assert!(ranks[&server.url("/b")] > ranks[&server.url("/a")]);
+    }
+
+    #[test]
+    fn test_crawl_capacities() {
+        assert!(FifoFrontier::with_capacity(1000).capacity() >= 1000);
+        assert!(HashVisitedSet::with_capacity(1000).capacity() >= 1000);
+
+        let server = TestServer::with_pages(&[
+            ("/", r#"<title>Home</title> <a href="/about">About</a>"#),
+            ("/about", "<title>About</title>"),
+        ]);
+        let crawler = Crawler::builder().with_frontier_capacity(1000).with_visited_capacity(1000).build().unwrap();
+        assert!(crawler.default_frontier().capacity() >= 1000);
+        assert!(crawler.default_visited_set().capacity() >= 1000);
+        assert_eq!(crawler.crawl(server.url("/")).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_crawl_allowed_ports() {
+        let server = TestServer::start(|req| match req.header("Host") {
+            Some("example.com") => Response::html(r#"<title>Home</title>
+                <a href="http://example.com:80/standard">Standard</a>
+                <a href="http://example.com:8080/">8080</a>
+                <a href="http://example.com:8081/">8081</a>"#),
+            _ => Response::html("<title>Other</title>"),
+        });
+        let crawl = |crawler: Crawler| {
+            let mut hosts: Vec<_> = crawler.crawl("http://example.com/").unwrap()
+                .map(|result| result.url[Position::BeforeHost..Position::AfterPath].to_owned())
+                .collect();
+            hosts.sort();
+            hosts
+        };
+
+        // By default, only standard ports are followed.
+        let crawler = Crawler::builder().resolve("example.com", server.addr()).build().unwrap();
+        assert_eq!(crawl(crawler), ["example.com/", "example.com/standard"]);
+        let crawler = Crawler::builder().resolve("example.com", server.addr()).allowed_ports(vec![8080, 8081]).build().unwrap();
+        assert_eq!(crawl(crawler), ["example.com/", "example.com/standard", "example.com:8080/", "example.com:8081/"]);
+        let crawler = Crawler::builder().resolve("example.com", server.addr()).allowed_ports(vec![8080]).build().unwrap();
+        assert_eq!(crawl(crawler), ["example.com/", "example.com/standard", "example.com:8080/"]);
+        let crawler = Crawler::builder().resolve("example.com", server.addr()).allowed_ports(vec![]).build().unwrap();
+        assert_eq!(crawl(crawler), ["example.com/", "example.com/standard"]);
+    }
+
+    #[test]
+    fn test_crawl_in_background() {
+        let (request_sender, request_receiver) = mpsc::channel();
+        let request_sender = Mutex::new(request_sender);
+        let server = TestServer::start(move |req| {
+            let _ = request_sender.lock().unwrap().send(req.path.clone());
+            let page: usize = req.path[1..].parse().unwrap_or(0);
+            Response::html(format!(r#"<title>Page {}</title><a href="/{}">Next</a>"#, page, page + 1))
+        });
+        let crawler = Crawler::builder().max_pages(10).build().unwrap();
+        let results = crawler.crawl_in_background(server.url("/"), 2).unwrap();
+
+        // With nobody receiving, the crawl stops once the channel is full (and one more result is waiting to be sent).
+        for path in &["/", "/1", "/2"] {
+            assert_eq!(request_receiver.recv_timeout(Duration::from_secs(10)).unwrap(), *path);
+        }
+        // Only a crawl that wrongly carries on could make another request here, however slow the machine is.
+        assert!(request_receiver.recv_timeout(Duration::from_millis(200)).is_err());
+        assert_eq!(server.requests().len(), 3);
+
+        assert_eq!(results.iter().count(), 10);
+        assert_eq!(server.requests().len(), 10);
+    }
+
+    #[test]
+    fn test_crawl_case_insensitive_hosts() {
+        let server = TestServer::start(|req| {
+            let html = match (req.header("Host"), &*req.path) {
+                (Some("example.com"), "/Path") => r#"<title>Path</title>
+                    <a href="http://EXAMPLE.com/Path">Again</a> <a href="HTTP://Example.Com/path">Lowercase</a>
+                    <a href="hTtP://eXaMpLe.CoM/Path/sub">Sub</a>"#,
+                (Some("example.com"), _) => "<title>Other</title>",
+                _ => return Response::new(404),
+            };
+            Response::html(html)
+        });
+
+        let crawler = Crawler::builder()
+            .resolve("Example.COM", server.addr())
+            .path_prefix("/Path")
+            .max_hosts(1)
+            .build()
+            .unwrap();
+        let urls: Vec<_> = crawler.crawl("HTTP://Example.COM/Path").unwrap().map(|result| result.url.to_string()).collect();
+        assert_eq!(urls, ["http://example.com/Path", "http://example.com/Path/sub"]);
+        assert_eq!(server.requested_paths(), ["/Path", "/Path/sub"]);
+    }
+
+    #[test]
+    fn test_crawl_prime_robots_txt() {
+        let server = TestServer::start(|req| match &*req.path {
+            "/robots.txt" => Response::new(200).header("Content-Type", "text/plain").body(
+                "User-agent: other\nDisallow: /\n\nUser-agent: *\nDisallow: /private\nAllow: /private/ok\n\nSitemap: /sitemap.xml\n"
+            ),
+            "/" => Response::html(r#"<title>Home</title>
+                <a href="/private">Private</a> <a href="/private/ok">OK</a> <a href="/public">Public</a>"#),
+            _ => Response::html("<title>Page</title>"),
+        });
+        let crawler = Crawler::builder().respect_robots_txt(true).build().unwrap();
+        assert_eq!(crawler.prime(vec![server.url("/")]), [server.url("/sitemap.xml")]);
+        assert_eq!(server.requested_paths(), ["/robots.txt"]);
+
+        let paths: Vec<_> = crawler.crawl(server.url("/")).unwrap().map(|result| result.url.path().to_owned()).collect();
+        assert_eq!(paths, ["/", "/private/ok", "/public"]);
+        assert_eq!(server.requested_paths(), ["/robots.txt", "/", "/private/ok", "/public"]);
+    }
+
+    #[test]
+    fn test_crawl_directory_index() {
+        let server = TestServer::with_pages(&[
+            ("/index.html", r#"<title>Home</title> <a href="/docs/">Docs</a> <a href="/docs/index.html">Docs again</a>"#),
+            ("/docs/index.html", "<title>Docs</title>"),
+        ]);
+        let crawler = Crawler::builder().directory_index("index.html").build().unwrap();
+        let paths: Vec<_> = crawler.crawl(server.url("/")).unwrap().map(|result| result.url.path().to_owned()).collect();
+        assert_eq!(paths, ["/index.html", "/docs/index.html"]);
+        assert_eq!(server.requested_paths(), ["/index.html", "/docs/index.html"]);
+
+        assert_eq!(Crawler::new().crawl(server.url("/")).unwrap().count(), 0);
+    }
+
+    #[cfg(not(feature = "nightly"))]
+    #[test]
+    fn test_crawl_iter() {
+        let server = TestServer::with_pages(&[
+            ("/", r#"<title>Home</title> <a href="/a">A</a> <a href="/b">B</a>"#),
+            ("/a", r#"<title>A</title> <a href="/">Home</a> <a href="/c">C</a>"#),
+            ("/b", "<title>B</title>"),
+            ("/c", "<title>C</title>"),
+        ]);
+        let mut crawl = Crawler::new().crawl(server.url("/")).unwrap();
+        let titles: Vec<_> = crawl.by_ref().map(|result| result.page.unwrap().title).collect();
+        assert_eq!(titles, ["Home", "A", "B", "C"]);
+        // Unlike a generator, the iterator may be polled again once it's finished.
+        assert!(crawl.next().is_none());
+    }
+
+    #[test]
+    fn test_audit() {
+        let server = TestServer::with_pages(&[
+            ("/", r#"<title>Home</title> <a href="/about">About</a> <a href="/missing">Missing</a> <a href="/gone#top">Gone</a>"#),
+            ("/about", r#"<title>About</title> <a href="/missing">Missing</a> <a href="/">Home</a>"#),
+        ]);
+        let report = Crawler::new().audit(server.url("/")).unwrap();
+        assert_eq!(report.broken_links, [
+            BrokenLink {
+                url: server.url("/missing"),
+                status: StatusCode::NOT_FOUND,
+                referrers: vec![server.url("/"), server.url("/about")],
+            },
+            BrokenLink {
+                url: server.url("/gone"),
+                status: StatusCode::NOT_FOUND,
+                referrers: vec![server.url("/")],
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_crawl_ordered() {
+        let other_server = TestServer::start_on("127.0.0.2", |_| Response::html("<title>Other</title>"));
+        let other_url = other_server.url("/b");
+        let server = TestServer::start(move |req| match &*req.path {
+            "/" => Response::html(format!(r#"<title>Home</title>
+                <a href="/a1">A1</a> <a href="/missing">Missing</a> <a href="/a2">A2</a> <a href="{}">B</a>"#, other_url)),
+            "/missing" => Response::not_found(),
+            _ => Response::html("<title>Page</title>"),
+        });
+        let crawl = |crawler: Crawler| -> Vec<_> {
+            crawler.crawl(server.url("/")).unwrap().map(|result| unshare_url(result.url)).collect()
+        };
+
+        // Taking hosts in turn fetches the other host's page before the last of this one's.
+        let other_ports = vec![other_server.addr().port()];
+        let crawler = Crawler::builder().interleave_hosts(true).allowed_ports(other_ports.clone()).build().unwrap();
+        assert_eq!(crawl(crawler), [server.url("/"), server.url("/a1"), other_server.url("/b"), server.url("/a2")]);
+        let crawler = Crawler::builder().interleave_hosts(true).ordered(true).allowed_ports(other_ports).build().unwrap();
+        assert_eq!(crawl(crawler), [server.url("/"), server.url("/a1"), server.url("/a2"), other_server.url("/b")]);
+    }
+
+    #[test]
+    fn test_external_domain_histogram() {
+        let server = TestServer::with_pages(&[
+            ("/", r#"<title>Home</title> <a href="/about">About</a>
+                <a href="http://b.example/">B</a> <a href="http://a.example/1">A</a> <a href="http://a.example/2">A</a>"#),
+            ("/about", r#"<title>About</title> <a href="/">Home</a>
+                <a href="http://a.example/1">A</a> <a href="http://c.example/">C</a> <a href="http://c.example/#top">C</a>"#),
+        ]);
+        let results: Vec<_> = Crawler::builder().max_hosts(1).build().unwrap().crawl(server.url("/")).unwrap().collect();
+        assert_eq!(external_domain_histogram(&results), [
+            ("a.example".to_owned(), 3),
+            ("b.example".to_owned(), 1),
+            ("c.example".to_owned(), 1),
+        ]);
+    }
+
+    #[test]
+    fn test_crawl_url_normalizer() {
+        let server = TestServer::with_pages(&[
+            ("/", r#"<title>Home</title> <a href="/news/story/amp">AMP</a> <a href="/news/story">Story</a>"#),
+            ("/news/story", "<title>Story</title>"),
+            ("/news/story/amp", "<title>Story (AMP)</title>"),
+        ]);
+        let crawler = Crawler::builder()
+            .url_normalizer(|url| {
+                let mut url = url.clone();
+                if let Some(path) = url.path().strip_suffix("/amp").map(str::to_owned) {
+                    url.set_path(&path);
+                }
+                url
+            })
+            .build()
+            .unwrap();
+        let paths: Vec<_> = crawler.crawl(server.url("/")).unwrap().map(|result| result.url.path().to_owned()).collect();
+        assert_eq!(paths, ["/", "/news/story"]);
+        assert_eq!(server.requested_paths(), ["/", "/news/story"]);
+    }
+
+    #[test]
+    fn test_crawl_max_total_bytes() {
+        // Each page is exactly 100 bytes.
+        let server = TestServer::start(|req| {
+            let page: usize = req.path[1..].parse().unwrap_or(0);
+            let html = format!(r#"<title>Page {}</title><a href="/{}">Next</a>"#, page, page + 1);
+            Response::html(format!("{:<100}", html))
+        });
+        let crawler = Crawler::builder().max_total_bytes(250).build().unwrap();
+        let results: Vec<_> = crawler.crawl(server.url("/")).unwrap().collect();
+        assert!(results.iter().all(|result| result.byte_size == 100));
+        assert_eq!(results.len(), 3);
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[test]
+    fn test_crawl_attempts() {
+        let server = TestServer::with_pages(&[
+            ("/", r#"<title>Home</title> <a href="/missing">Missing</a> <a href="/about">About</a>"#),
+            ("/about", "<title>About</title>"),
+        ]);
+        let attempts: Vec<_> = Crawler::new().crawl_attempts(server.url("/")).unwrap().collect();
+        assert_eq!(attempts.len(), 3);
+        assert_eq!(*attempts[0].as_ref().unwrap().url, server.url("/"));
+        assert!(is_match!(&attempts[1], Err(failure) if *failure.url == server.url("/missing")));
+        assert!(is_match!(attempts[1].as_ref().unwrap_err().error, FetchWebPageError::BadHttpStatus(StatusCode::NOT_FOUND)));
+        assert_eq!(*attempts[2].as_ref().unwrap().url, server.url("/about"));
+
+        let titles: Vec<_> = Crawler::new().crawl_attempts(server.url("/")).unwrap()
+            .successes_only()
+            .map(|result| result.page.unwrap().title)
+            .collect();
+        assert_eq!(titles, ["Home", "About"]);
+    }
+
+    #[test]
+    fn test_crawl_soft_404() {
+        let server = TestServer::with_pages(&[
+            ("/", r#"<title>Home</title> <a href="/old">Old</a> <a href="/about">About</a>"#),
+            ("/old", r#"<title>Oops</title> <p>Sorry, this page could not be found.</p> <a href="/search">Search</a>"#),
+            ("/about", "<title>About</title> <p>About us.</p>"),
+            ("/search", "<title>Search</title>"),
+        ]);
+        let crawler = Crawler::builder()
+            .soft_404(Soft404Config {
+                patterns: vec!["Could Not Be Found".to_owned()],
+                ..Soft404Config::default()
+            })
+            .build()
+            .unwrap();
+        let results: Vec<_> = crawler.crawl(server.url("/")).unwrap().collect();
+        let soft_404s: Vec<_> = results.iter().map(|result| (result.url.path(), result.soft_404)).collect();
+        assert_eq!(soft_404s, [("/", false), ("/old", true), ("/about", false)]);
+        assert!(!server.requested_paths().contains(&"/search".to_owned()));
+
+        let crawler = Crawler::builder()
+            .soft_404(Soft404Config {
+                max_body_text_len: Some(0),
+                error_page_hashes: vec![Soft404Config::hash_page(r#"<title>Oops</title> <p>Sorry, this page could not be found.</p> <a href="/search">Search</a>"#)],
+                ..Soft404Config::default()
+            })
+            .build()
+            .unwrap();
+        let soft_404s: Vec<_> = crawler.crawl(server.url("/")).unwrap().map(|result| (result.url.path().to_owned(), result.soft_404)).collect();
+        assert_eq!(soft_404s, [("/".to_owned(), false), ("/old".to_owned(), true), ("/about".to_owned(), false)]);
+    }
+
+    #[test]
+    fn test_relative_link_resolution() {
+        // NOTE: links are resolved as per the URL standard, under which the last path segment is only a directory if it
+        // ends in a slash. Don't "fix" this: browsers resolve links the same way.
+        let doc = Document::from(r#"<title>Docs</title> <a href="about">About</a> <a href="../up">Up</a> <a href="?q">Query</a>"#);
+        let page = get_web_page_info(&"https://x.com/docs".parse().unwrap(), &doc).unwrap();
+        let links: Vec<_> = page.links.iter().map(Url::as_str).collect();
+        assert_eq!(links, ["https://x.com/about", "https://x.com/up", "https://x.com/docs?q"]);
+
+        let page = get_web_page_info(&"https://x.com/docs/".parse().unwrap(), &doc).unwrap();
+        let links: Vec<_> = page.links.iter().map(Url::as_str).collect();
+        assert_eq!(links, ["https://x.com/docs/about", "https://x.com/up", "https://x.com/docs/?q"]);
+    }
+
+    #[test]
+    fn test_extract_links() {
+        let html = r#"<base href="/docs/"> <a href="intro">Intro</a> <a href="/">Home</a> <a href="intro">Intro again</a> <a>None</a>"#;
+        let links = extract_links(&"https://x.com/about".parse().unwrap(), html);
+        let links: Vec<_> = links.iter().map(Url::as_str).collect();
+        assert_eq!(links, ["https://x.com/docs/intro", "https://x.com/"]);
+    }
+
+    #[test]
+    fn test_crawl_chunked_body_size() {
+        const HOME: &str = r#"<title>Home</title> <a href="/big">Big</a> <a href="/small">Small</a>"#;
+        let big_page = format!("<title>Big</title> {}", "x".repeat(200));
+        let server = TestServer::start(move |req| {
+            let body = match &*req.path {
+                "/" => HOME.to_owned(),
+                "/big" => big_page.clone(),
+                "/small" => "<title>Small</title>".to_owned(),
+                _ => return Response::not_found(),
+            };
+            Response::html(body).header("Transfer-Encoding", "chunked")
+        });
+
+        let options = FetchOptions {
+            max_body_size: Some(100),
+            ..FetchOptions::default()
+        };
+        let client = Client::new();
+        let err = fetch_web_page(client.get(server.url("/big")), &options).err().unwrap();
+        assert!(is_match!(err, FetchWebPageError::BodyTooLarge(100)));
+
+        let crawler = Crawler::builder().max_body_size(100).build().unwrap();
+        let sizes: Vec<_> = crawler.crawl(server.url("/")).unwrap().map(|result| (result.url.path().to_owned(), result.byte_size)).collect();
+        assert_eq!(sizes, [("/".to_owned(), HOME.len()), ("/small".to_owned(), "<title>Small</title>".len())]);
+    }
+
+    #[test]
+    fn test_crawl_fetch_url_rewriter() {
+        let staging = TestServer::start(|req| {
+            match (req.header("Host"), &*req.path) {
+                (Some("staging.example.com"), "/") => Response::html(r#"<title>Home</title> <a href="http://prod.example.com/about">About</a>"#),
+                (Some("staging.example.com"), "/about") => Response::html("<title>About</title>"),
+                _ => Response::not_found(),
+            }
+        });
+        let crawler = Crawler::builder()
+            .resolve("staging.example.com", staging.addr())
+            .fetch_url_rewriter(|url| {
+                let mut url = url.clone();
+                if url.host_str() == Some("prod.example.com") {
+                    url.set_host(Some("staging.example.com")).unwrap();
+                }
+                url
+            })
+            .build()
+            .unwrap();
+        let results: Vec<_> = crawler.crawl("http://prod.example.com/").unwrap().collect();
+        let urls: Vec<_> = results.iter().map(|result| result.url.as_str()).collect();
+        assert_eq!(urls, ["http://prod.example.com/", "http://prod.example.com/about"]);
+        assert_eq!(results[0].page.as_ref().unwrap().links, [Url::parse("http://prod.example.com/about").unwrap()]);
+        assert_eq!(staging.requested_paths(), ["/", "/about"]);
+    }
+
+    #[test]
+    fn test_crawl_max_depth_per_host() {
+        // Each host serves a chain of pages `/`, `/1`, `/2`, ..., and its home page also links to the other host.
+        fn chain(req: &test_server::Request, other_host: &str) -> Response {
+            let depth: usize = req.path[1..].parse().unwrap_or(0);
+            let other_link = if depth == 0 { format!(r#"<a href="http://{}/">Other</a>"#, other_host) } else { String::new() };
+            Response::html(format!(r#"<title>Page {}</title> <a href="/{}">Next</a> {}"#, depth, depth + 1, other_link))
+        }
+        let server = TestServer::start(|req| {
+            match req.header("Host") {
+                Some("mine.example.com") => chain(req, "other.example.com"),
+                _ => chain(req, "mine.example.com"),
+            }
+        });
+        let crawler = Crawler::builder()
+            .resolve("mine.example.com", server.addr())
+            .resolve("other.example.com", server.addr())
+            .max_depth(1)
+            .max_depth_for_host("Mine.example.com", 3)
+            .max_hosts(2)
+            .build()
+            .unwrap();
+        let mut urls: Vec<_> = crawler.crawl("http://mine.example.com/").unwrap().map(|result| result.url.to_string()).collect();
+        urls.sort();
+        assert_eq!(urls, [
+            "http://mine.example.com/",
+            "http://mine.example.com/1",
+            "http://mine.example.com/2",
+            "http://mine.example.com/3",
+            "http://other.example.com/",
+            "http://other.example.com/1",
+        ]);
+
+        // Page `n` of each host only links to page `n + 1` of the other, so the crawl keeps crossing between them.
+        let server = TestServer::start(|req| {
+            let page: usize = req.path[1..].parse().unwrap_or(0);
+            let other_host = if req.header("Host") == Some("mine.example.com") { "other.example.com" } else { "mine.example.com" };
+            Response::html(format!(r#"<title>Page {}</title> <a href="http://{}/{}">Next</a>"#, page, other_host, page + 1))
+        });
+        let crawler = Crawler::builder()
+            .resolve("mine.example.com", server.addr())
+            .resolve("other.example.com", server.addr())
+            .max_depth(2)
+            .max_pages(20)
+            .build()
+            .unwrap();
+        let urls: Vec<_> = crawler.crawl("http://mine.example.com/0").unwrap().map(|result| result.url.to_string()).collect();
+        assert_eq!(urls, [
+            "http://mine.example.com/0",
+            "http://other.example.com/1",
+            "http://mine.example.com/2",
+            "http://other.example.com/3",
+        ]);
+    }
+
+    #[test]
+    fn test_diff() {
+        let redesigned = Arc::new(AtomicBool::new(false));
+        let server_redesigned = redesigned.clone();
+        let server = TestServer::start(move |req| {
+            let redesigned = server_redesigned.load(Ordering::SeqCst);
+            match &*req.path {
+                "/" if redesigned => Response::html(r#"<title>Home</title> <a href="/about">About</a> <a href="/blog">Blog</a>"#),
+                "/" => Response::html(r#"<title>Home</title> <a href="/about">About</a> <a href="/news">News</a>"#),
+                "/about" if redesigned => Response::html("<title>About us</title>"),
+                "/about" => Response::html("<title>About</title>"),
+                "/news" if !redesigned => Response::html("<title>News</title>"),
+                "/blog" if redesigned => Response::html("<title>Blog</title>"),
+                _ => Response::not_found(),
+            }
+        });
+        let previous: Vec<_> = Crawler::new().crawl(server.url("/")).unwrap().collect();
+        redesigned.store(true, Ordering::SeqCst);
+        let current: Vec<_> = Crawler::new().crawl(server.url("/")).unwrap().collect();
+
+        // Without the bodies, only the title tells whether a page changed, so the home page doesn't count.
+        assert_eq!(diff(&previous, &current), CrawlDiff {
+            added: vec![server.url("/blog")],
+            removed: vec![server.url("/news")],
+            changed: vec![server.url("/about")],
+        });
+        assert_eq!(diff(&current, &current), CrawlDiff::default());
+    }
+
+    #[test]
+    fn test_crawl_internal_links() {
+        let server = TestServer::with_pages(&[
+            ("/", r#"<title>Home</title> <a href="/about">About</a>"#),
+            ("/about", r#"<title>About</title> <a href="/">Home</a> <a href="http://example.com/">Example</a>"#),
+        ]);
+        let crawler = Crawler::builder().max_hosts(1).build().unwrap();
+        let results: Vec<_> = crawler.crawl(server.url("/")).unwrap().collect();
+        assert_eq!(results[1].page.as_ref().unwrap().link_details, [
+            Link { url: server.url("/"), internal: true },
+            Link { url: "http://example.com/".parse().unwrap(), internal: false },
+        ]);
+
+        // Outside of a crawl, links are internal if they're to the same host as the page itself.
+        let doc = Document::from(r#"<title>Links</title> <a href="/a">A</a> <a href="http://other.com/">Other</a>"#);
+        let page = get_web_page_info(&"http://example.com/".parse().unwrap(), &doc).unwrap();
+        let internal: Vec<_> = page.link_details.iter().map(|link| link.internal).collect();
+        assert_eq!(internal, [true, false]);
+    }
+
+    #[test]
+    fn test_crawl_cycles_visit_each_page_once() {
+        let server = TestServer::with_pages(&[
+            ("/", r#"<title>Root</title> <a href="/a">A</a> <a href="/b">B</a>"#),
+            ("/a", r#"<title>A</title> <a href="/">Root</a> <a href="/b">B</a>"#),
+            ("/b", r#"<title>B</title> <a href="/a">A</a> <a href="/c">C</a>"#),
+            ("/c", r#"<title>C</title> <a href="/">Root</a>"#),
+        ]);
+        let crawl_paths = |crawler: Crawler| -> Vec<_> {
+            crawler.crawl(server.url("/")).unwrap().map(|result| result.url.path().to_owned()).collect()
+        };
+        let paths = crawl_paths(Crawler::new());
+        assert_eq!(paths, ["/", "/a", "/b", "/c"]);
+        let crawler = Crawler::builder().visited_set(|| Box::new(HashVisitedSet::with_capacity(100))).build().unwrap();
+        assert_eq!(crawl_paths(crawler), paths);
+    }
+
+    #[test]
+    fn test_crawl_shares_urls() {
+        struct RecordingVisitedSet(HashVisitedSet, Arc<Mutex<Vec<Arc<Url>>>>);
+
+        impl VisitedSet for RecordingVisitedSet {
+            fn insert(&mut self, url: &Arc<Url>) {
+                self.1.lock().unwrap().push(Arc::clone(url));
+                self.0.insert(url);
+            }
+
+            fn contains(&self, url: &Url) -> bool {
+                self.0.contains(url)
+            }
+        }
+
+        let server = TestServer::start(|req| match &*req.path {
+            "/" => Response::html(r#"<title>Root</title> <a href="/a">A</a> <a href="/old">Old</a>"#),
+            "/a" => Response::html(r#"<title>A</title> <a href="/">Root</a>"#),
+            "/old" => Response::new(301).header("Location", "/new"),
+            "/new" => Response::html("<title>New</title>"),
+            _ => Response::not_found(),
+        });
+        let inserted = Arc::new(Mutex::new(vec![]));
+        let visited_inserted = inserted.clone();
+        let crawler = Crawler::builder()
+            .visited_set(move || Box::new(RecordingVisitedSet(HashVisitedSet::new(), visited_inserted.clone())))
+            .build()
+            .unwrap();
+        let results: Vec<_> = crawler.crawl(server.url("/")).unwrap().collect();
+        let paths: Vec<_> = results.iter().map(|result| result.url.path()).collect();
+        assert_eq!(paths, ["/", "/a", "/new"]);
+
+        // Each result has the very URL that was marked as visited (and queued), even when reached by a redirect.
+        let inserted = inserted.lock().unwrap();
+        for result in &results {
+            assert!(inserted.iter().any(|url| Arc::ptr_eq(url, &result.url)), "{} was copied", result.url);
+        }
+    }
+
+    #[test]
+    fn test_crawl_treat_www_as_same() {
+        let server = TestServer::start(|req| match &*req.path {
+            "/" => Response::html(r#"
+                <title>Home</title>
+                <a href="http://www.example.com/about">About</a> <a href="http://example.com/about">About</a>
+                <a href="http://www.example.com/contact">Contact</a>
+            "#),
+            "/about" => Response::html("<title>About</title>"),
+            "/contact" => Response::html("<title>Contact</title>"),
+            _ => Response::not_found(),
+        });
+        let crawler = Crawler::builder()
+            .resolve("example.com", server.addr())
+            .resolve("www.example.com", server.addr())
+            .path_prefix("/")
+            .treat_www_as_same(true)
+            .build()
+            .unwrap();
+        let results: Vec<_> = crawler.crawl("http://example.com/").unwrap().collect();
+        let urls: Vec<_> = results.iter().map(|result| result.url.as_str()).collect();
+        assert_eq!(urls, ["http://example.com/", "http://example.com/about", "http://example.com/contact"]);
+        assert!(results[0].page.as_ref().unwrap().link_details.iter().all(|link| link.internal));
+
+        // Otherwise, the `www.` host is a different one, outside the path prefix.
+        let crawler = Crawler::builder()
+            .resolve("example.com", server.addr())
+            .resolve("www.example.com", server.addr())
+            .path_prefix("/")
+            .build()
+            .unwrap();
+        let urls: Vec<_> = crawler.crawl("http://example.com/").unwrap().map(|result| result.url.to_string()).collect();
+        assert_eq!(urls, ["http://example.com/", "http://example.com/about"]);
+    }
+
+    #[test]
+    fn test_parse_timeout() {
+        let url: Url = "http://example.com/".parse().unwrap();
+        let huge_page = format!("<title>Huge</title> {}", r#"<div><a href="/page">Page</a> <img src="/image.png"></div>"#.repeat(50_000));
+        let start = Instant::now();
+        let _ = Document::from(&*huge_page);
+        let parse_time = start.elapsed();
+
+        let start = Instant::now();
+        let err = document_info_within(&url, &huge_page, &["href".to_owned()], None, Duration::from_millis(10)).err().unwrap();
+        assert!(is_match!(err, GetWebPageInfoError::ParseTimeout(_)));
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        let info = document_info_within(&url, "<title>Small</title>", &["href".to_owned()], None, Duration::from_secs(10)).unwrap();
+        assert_eq!(info.page.title, "Small");
+        // Everything else that needs the document comes from the same parse.
+        let not_found = r#"<title>Not Found</title> <meta property="article:modified_time" content="2020-01-02">"#;
+        let soft_404 = Soft404Config {
+            patterns: vec!["not found".to_owned()],
+            ..Soft404Config::default()
+        };
+        let info = document_info_within(&url, not_found, &["href".to_owned()], Some(&soft_404), Duration::from_secs(10)).unwrap();
+        assert!(info.soft_404);
+        assert!(info.last_modified.is_some());
+
+        let server = TestServer::with_pages(&[
+            ("/", r#"<title>Home</title> <a href="/huge">Huge</a> <a href="/small">Small</a>"#),
+            ("/huge", &huge_page),
+            ("/small", "<title>Small</title>"),
+        ]);
+        let crawler = Crawler::builder().parse_timeout(Duration::from_millis(50)).build().unwrap();
+        let start = Instant::now();
+        let paths: Vec<_> = crawler.crawl(server.url("/")).unwrap().map(|result| result.url.path().to_owned()).collect();
+        assert_eq!(paths, ["/", "/small"]);
+        // The crawl itself never parsed the huge page in full.
+        assert!(start.elapsed() < parse_time / 2);
+    }
+
+    #[test]
+    fn test_parse_document_by() {
+        let html = r#"<!DOCTYPE html><title>Page</title> <!-- comment --> <p class="x">Text <a href="/a">A</a><p>More"#;
+        let deadline = Instant::now() + Duration::from_secs(10);
+        assert_eq!(parse::parse_document_by(html, deadline), Some(Document::from(html)));
+        let long_html = format!("<title>Long</title> {}", "<p>Ünïcödé</p>".repeat(10_000));
+        assert_eq!(parse::parse_document_by(&long_html, deadline), Some(Document::from(&*long_html)));
+        assert_eq!(parse::parse_document_by(html, Instant::now()), None);
+    }
+
+    #[test]
+    fn test_crawl_rate_limit() {
+        let server = TestServer::start(|req| {
+            let page: usize = req.path[1..].parse().unwrap_or(0);
+            Response::html(format!(r#"<title>Page {}</title> <a href="/{}">Next</a>"#, page, page + 1))
+        });
+        let crawler = Crawler::builder().rate_limit(20.0).max_pages(50).build().unwrap();
+        let start = Instant::now();
+        assert_eq!(crawler.crawl(server.url("/")).unwrap().count(), 50);
+        // After the first second's worth, the rest are spread out over (at least) the next 1.5 seconds.
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(1400), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(5), "{:?}", elapsed);
+    }
+
+    #[test]
+    fn test_crawl_rate_limit_retries() {
+        let server = TestServer::start(|_| Response::new(503));
+        // Every attempt takes a token: after the first second's worth (2), the other 3 take (at least) 1.5 seconds.
+        let crawler = Crawler::builder().rate_limit(2.0).max_retries(4).build().unwrap();
+        let start = Instant::now();
+        assert_eq!(crawler.crawl(server.url("/")).unwrap().count(), 0);
+        let elapsed = start.elapsed();
+        assert_eq!(server.requests().len(), 5);
+        assert!(elapsed >= Duration::from_millis(1400), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(5), "{:?}", elapsed);
+
+        // So does each redirect followed.
+        let server = TestServer::start(|req| match &*req.path {
+            "/" => Response::new(301).header("Location", "/a"),
+            "/a" => Response::new(301).header("Location", "/b"),
+            _ => Response::html("<title>B</title>"),
+        });
+        let start = Instant::now();
+        assert_eq!(crawler.crawl(server.url("/")).unwrap().count(), 1);
+        let elapsed = start.elapsed();
+        assert_eq!(server.requested_paths(), ["/", "/a", "/b"]);
+        assert!(elapsed >= Duration::from_millis(400), "{:?}", elapsed);
+    }
+
+    #[test]
+    fn test_crawl_redirect_chain() {
+        let server = TestServer::start(|req| match &*req.path {
+            "/" => Response::html(r#"<title>Home</title> <a href="/old">Old</a>"#),
+            "/old" => Response::new(301).header("Location", "/older"),
+            "/older" => Response::new(302).header("Location", "/new"),
+            "/new" => Response::html("<title>New</title>"),
+            _ => Response::not_found(),
+        });
+        let results: Vec<_> = Crawler::new().crawl(server.url("/")).unwrap().collect();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].redirect_chain.is_empty());
+        assert_eq!(*results[1].url, server.url("/new"));
+        assert_eq!(results[1].redirect_chain, [
+            (server.url("/old"), StatusCode::MOVED_PERMANENTLY),
+            (server.url("/older"), StatusCode::FOUND),
+        ]);
+    }
+
+    #[test]
+    fn test_crawl_link_attributes() {
+        let server = TestServer::with_pages(&[
+            ("/", r#"<title>Home</title> <a href="/a">A</a> <a data-href="/b">B</a> <a href="/c" data-url="/d">C</a>"#),
+            ("/a", "<title>A</title>"),
+            ("/b", "<title>B</title>"),
+            ("/c", "<title>C</title>"),
+            ("/d", "<title>D</title>"),
+        ]);
+        let crawl_paths = |crawler: Crawler| -> Vec<_> {
+            crawler.crawl(server.url("/")).unwrap().map(|result| result.url.path().to_owned()).collect()
+        };
+        assert_eq!(crawl_paths(Crawler::new()), ["/", "/a", "/c"]);
+        let crawler = Crawler::builder().link_attributes(vec!["href", "data-href", "data-url"]).build().unwrap();
+        assert_eq!(crawl_paths(crawler), ["/", "/a", "/b", "/c", "/d"]);
+    }
+
+    #[test]
+    fn test_crawl_filtered() {
+        let server = TestServer::with_pages(&[
+            ("/", r#"<title>Home</title> <a href="/products">Products</a>"#),
+            ("/products", r#"<title>Products</title> <a href="/products/1">1</a> <a href="/products/2">2</a>"#),
+            ("/products/1", "<title>Product: Widget</title>"),
+            ("/products/2", "<title>Product: Gadget</title>"),
+        ]);
+        let titles: Vec<_> = Crawler::new().crawl_filtered(server.url("/"), |page| page.title.starts_with("Product:")).unwrap()
+            .map(|result| result.page.unwrap().title)
+            .collect();
+        assert_eq!(titles, ["Product: Widget", "Product: Gadget"]);
+        assert_eq!(server.requested_paths(), ["/", "/products", "/products/1", "/products/2"]);
+    }
+
+    #[test]
+    fn test_find_mixed_content() {
+        let url: Url = "https://example.com/".parse().unwrap();
+        let doc = Document::from(r#"
+            <title>Mixed</title>
+            <link rel="stylesheet" href="http://cdn.example.com/style.css">
+            <link rel="alternate" href="http://example.com/feed.xml">
+            <img src="http://example.com/image.png"> <img src="/secure.png">
+            <script src="http://cdn.example.com/script.js"></script>
+            <a href="http://example.com/page">Not a subresource</a>
+        "#);
+        let page = get_web_page_info(&url, &doc).unwrap();
+        let mixed_content = find_mixed_content(&url, &doc, &page);
+        assert_eq!(mixed_content.iter().map(Url::as_str).collect::<Vec<_>>(), [
+            "http://example.com/image.png",
+            "http://cdn.example.com/script.js",
+            "http://cdn.example.com/style.css",
+        ]);
+
+        // Pages fetched over plain HTTP are insecure anyway, so they're not checked.
+        let server = TestServer::with_pages(&[("/", r#"<title>Home</title> <img src="http://example.com/image.png">"#)]);
+        let results: Vec<_> = Crawler::new().crawl(server.url("/")).unwrap().collect();
+        assert!(!results[0].secure);
+        assert!(results[0].mixed_content.is_empty());
+    }
+
+    #[test]
+    fn test_crawl_max_total_retries() {
+        fn home() -> Response {
+            Response::html(format!("<title>Home</title> {}", (1..=10).map(|i| format!(r#"<a href="/{0}">{0}</a>"#, i)).collect::<String>()))
+        }
+
+        let server = TestServer::start(|req| match &*req.path {
+            "/" => home(),
+            "/5" => Response::html("<title>Fine</title>"),
+            _ => Response::new(503),
+        });
+        let crawler = Crawler::builder().max_retries(2).build().unwrap();
+        assert_eq!(crawler.crawl(server.url("/")).unwrap().count(), 2);
+        // Each of the 9 failing pages is tried 3 times.
+        assert_eq!(server.requests().len(), 2 + 9 * 3);
+
+        let server = TestServer::start(|req| match &*req.path {
+            "/" => home(),
+            _ => Response::new(503),
+        });
+        let crawler = Crawler::builder().max_retries(2).max_total_retries(5).build().unwrap();
+        assert_eq!(crawler.crawl(server.url("/")).unwrap().count(), 1);
+        assert_eq!(server.requests().len(), 1 + 10 + 5);
+    }
+
+    #[test]
+    fn test_crawl_last_modified() {
+        let server = TestServer::start(|req| match &*req.path {
+            "/" => Response::html(r#"
+                <title>Home</title> <meta property="article:modified_time" content="2001-01-01T00:00:00Z">
+                <a href="/article">Article</a> <a href="/undated">Undated</a>
+            "#).header("Last-Modified", "Wed, 21 Oct 2015 07:28:00 GMT"),
+            "/article" => Response::html(r#"
+                <title>Article</title> <meta property="article:modified_time" content="2015-10-21T09:28:00.5+02:00">
+            "#),
+            "/undated" => Response::html("<title>Undated</title>"),
+            _ => Response::not_found(),
+        });
+        let results: Vec<_> = Crawler::new().crawl(server.url("/")).unwrap().collect();
+        let last_modified: Vec<_> = results.iter()
+            .map(|result| result.last_modified.map(|last_modified| time::at_utc(last_modified).rfc3339().to_string()))
+            .collect();
+        assert_eq!(last_modified, [Some("2015-10-21T07:28:00Z".to_owned()), Some("2015-10-21T07:28:00Z".to_owned()), None]);
+
+        assert_eq!(parse_iso8601("2015-10-21"), parse_iso8601("2015-10-21T00:00:00Z"));
+        assert_eq!(parse_iso8601("2015-10-21T07:28:00-0130"), parse_iso8601("2015-10-21T08:58:00Z"));
+        assert_eq!(parse_iso8601("21 October 2015"), None);
+    }
+
+    #[test]
+    fn test_crawl_trailing_slash() {
+        let server = TestServer::with_pages(&[
+            ("/", r#"<title>Home</title> <a href="/docs">Docs</a> <a href="/docs/">Docs again</a> <a href="/a.html">A</a>"#),
+            ("/docs", r#"<title>Docs</title> <a href="/a.html/">A again</a>"#),
+            ("/docs/", r#"<title>Docs</title> <a href="/a.html/">A again</a>"#),
+            ("/a.html", "<title>A</title>"),
+            ("/a.html/", "<title>A</title>"),
+        ]);
+        let paths = |trailing_slash| {
+            let crawler = Crawler::builder().trailing_slash(trailing_slash).build().unwrap();
+            crawler.crawl(server.url("/")).unwrap().map(|result| result.url.path().to_owned()).collect::<Vec<_>>()
+        };
+        assert_eq!(paths(TrailingSlash::Preserve), ["/", "/docs", "/docs/", "/a.html", "/a.html/"]);
+        assert_eq!(paths(TrailingSlash::AddToDirectories), ["/", "/docs/", "/a.html", "/a.html/"]);
+        assert_eq!(paths(TrailingSlash::Strip), ["/", "/docs", "/a.html"]);
+    }
+
+    #[test]
+    fn test_display_crawl_result() {
+        let server = TestServer::start(|req| match &*req.path {
+            "/" => Response::html(r#"<title>Home</title> <a href="/a">A</a> <a href="/b.pdf">B</a>"#),
+            "/a" => Response::html(r#"<title>A</title> <a href="/">Home</a>"#),
+            "/b.pdf" => Response::new(200).header("Content-Type", "application/pdf").body("%PDF-1.4"),
+            _ => Response::not_found(),
+        });
+        let crawler = Crawler::builder().extra_content_type("application/pdf").build().unwrap();
+        let summaries: Vec<_> = crawler.crawl(server.url("/")).unwrap().map(|result| result.to_string()).collect();
+        assert_eq!(summaries, [
+            format!("{} — \"Home\" (2 links)", server.url("/")),
+            format!("{} — \"A\" (1 link)", server.url("/a")),
+            format!("{} — application/pdf (8 bytes)", server.url("/b.pdf")),
+        ]);
+    }
+
+    #[test]
+    fn test_crawl_token_provider() {
+        let server = TestServer::start(|req| match req.header("Authorization") {
+            Some("Bearer fresh") => Response::html("<title>Home</title>"),
+            _ => Response::new(401),
+        });
+        let tokens = Arc::new(Mutex::new(vec!["fresh", "expired"]));
+        let crawler = Crawler::builder()
+            .token_provider(move || {
+                let mut tokens = tokens.lock().unwrap();
+                // Keep handing out the last token once the others are used up.
+                if tokens.len() > 1 { tokens.pop() } else { tokens.last().cloned() }.unwrap().to_owned()
+            })
+            .build()
+            .unwrap();
+        let titles: Vec<_> = crawler.crawl(server.url("/")).unwrap().map(|result| result.page.unwrap().title).collect();
+        assert_eq!(titles, ["Home"]);
+        let authorizations: Vec<_> = server.requests().iter()
+            .filter(|req| req.path == "/")
+            .map(|req| req.header("Authorization").unwrap_or_default().to_owned())
+            .collect();
+        assert_eq!(authorizations, ["Bearer expired", "Bearer fresh"]);
+    }
+
+    #[test]
+    fn test_streaming_link_extraction() {
+        const HTML: &str = r#"
+            <html><head><base href="/docs/"><title> Docs &amp; more </title></head>
+            <body>
+                <a href="a">A</a> <a href="/b" type="application/PDF; q=1">B</a> <a>No link</a> <a href="a">A again</a>
+                <map><area href="c"></map>
+                <script>document.write('<a href="/not-a-link">');</script>
+                <textarea><a href="/not-a-link-either"></textarea>
+                <a href="http://[::1">Invalid</a> <a href="d%7e">D</a>
+            </body></html>
+        "#;
+        let url: Url = "http://example.com/".parse().unwrap();
+        let link_attributes = ["href".to_owned()];
+        let streamed = streaming::get_web_page_info(&url, HTML, &link_attributes).unwrap();
+        let parsed = get_web_page_info(&url, &Document::from(HTML)).unwrap();
+        assert_eq!(streamed.title, parsed.title);
+        assert_eq!(streamed.links, parsed.links);
+        assert_eq!(streamed.link_types, parsed.link_types);
+        assert_eq!(streamed.link_details, parsed.link_details);
+        assert_eq!(streamed.links.len(), 4);
+
+        assert!(streaming::get_web_page_info(&url, "<a href=\"/\">Home</a>", &link_attributes).is_err());
+
+        let server = TestServer::with_pages(&[
+            ("/", r#"<title>Home</title> <a href="/a">A</a>"#),
+            ("/a", r#"<title>A</title> <a href="/">Home</a> <a href="/b">B</a>"#),
+            ("/b", "<title>B</title>"),
+        ]);
+        let crawler = Crawler::builder().link_extraction(LinkExtraction::Streaming).build().unwrap();
+        let results: Vec<_> = crawler.crawl_with_documents(server.url("/")).unwrap().collect();
+        let titles: Vec<_> = results.iter().map(|(result, _)| result.page.as_ref().unwrap().title.clone()).collect();
+        assert_eq!(titles, ["Home", "A", "B"]);
+        assert!(results.iter().all(|(_, document)| document.is_none()));
+    }
+
+    #[test]
+    fn test_crawl_preseed_visited() {
+        let server = TestServer::with_pages(&[
+            ("/", r#"<title>Home</title> <a href="/old">Old</a> <a href="/new">New</a>"#),
+            ("/old", r#"<title>Old</title> <a href="/older">Older</a>"#),
+            ("/new", "<title>New</title>"),
+        ]);
+        let crawler = Crawler::builder().preseed_visited(vec![server.url("/old#top")]).build().unwrap();
+        let paths: Vec<_> = crawler.crawl(server.url("/")).unwrap().map(|result| result.url.path().to_owned()).collect();
+        assert_eq!(paths, ["/", "/new"]);
+        assert_eq!(server.requested_paths(), ["/", "/new"]);
+    }
+
+    #[test]
+    fn test_crawl_www_fallback() {
+        let server = TestServer::with_pages(&[
+            ("/", r#"<title>Home</title> <a href="http://www.example.test/a">A</a>"#),
+            ("/a", "<title>A</title>"),
+        ]);
+        // NOTE: the `.test` domain is reserved, so `www.example.test` never resolves.
+        let crawler = Crawler::builder().resolve("example.test", server.addr()).www_fallback(true).build().unwrap();
+        let urls: Vec<_> = crawler.crawl("http://www.example.test/").unwrap().map(|result| result.url.to_string()).collect();
+        assert_eq!(urls, ["http://example.test/", "http://example.test/a"]);
+
+        let crawler = Crawler::builder().resolve("example.test", server.addr()).build().unwrap();
+        assert_eq!(crawler.crawl("http://www.example.test/").unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_crawl_links_followed() {
+        let server = TestServer::with_pages(&[
+            ("/", r#"
+                <title>Home</title>
+                <a href="/a">A</a> <a href="/b">B</a> <a href="/c">C</a> <a href="/a">A again</a>
+                <a href="/private/d">D</a> <a href="http://example.com/">Elsewhere</a>
+            "#),
+            ("/a", r#"<title>A</title> <a href="/">Home</a> <a href="/b">B</a>"#),
+        ]);
+        let crawler = Crawler::builder().path_prefix("/a").path_prefix("/b").path_prefix("/c").build().unwrap();
+        let results: Vec<_> = crawler.crawl(server.url("/")).unwrap().collect();
+        assert_eq!((results[0].links_total, results[0].links_followed), (5, 3));
+        // Links to pages already visited or queued aren't followed again.
+        assert_eq!((results[1].links_total, results[1].links_followed), (2, 0));
+    }
+
+    #[test]
+    fn test_write_csv() {
+        let server = TestServer::start(|req| match &*req.path {
+            "/" => Response::html(r#"<title>Home, "sweet"
+home</title> <a href="/a">A</a> <a href="/b.pdf">B</a>"#),
+            "/a" => Response::html("<title>A</title>"),
+            "/b.pdf" => Response::new(200).header("Content-Type", "application/pdf").body("%PDF-1.4"),
+            _ => Response::not_found(),
+        });
+        let crawler = Crawler::builder().extra_content_type("application/pdf").build().unwrap();
+        let results: Vec<_> = crawler.crawl(server.url("/")).unwrap().collect();
+        let mut csv = vec![];
+        write_csv(&results, &mut csv).unwrap();
+
+        assert!(String::from_utf8(csv.clone()).unwrap().ends_with("\r\n"));
+
+        let mut reader = ::csv::ReaderBuilder::new().has_headers(false).from_reader(&*csv);
+        let rows: Vec<Vec<String>> = reader.records()
+            .map(|record| record.unwrap().iter().map(str::to_owned).collect())
+            .collect();
+        assert_eq!(rows, [
+            vec!["url", "title", "status", "depth", "link_count", "byte_size"],
+            vec![server.url("/").as_str(), "Home, \"sweet\"\nhome", "200", "0", "2", &results[0].byte_size.to_string()],
+            vec![server.url("/a").as_str(), "A", "200", "1", "0", "16"],
+            vec![server.url("/b.pdf").as_str(), "", "200", "1", "0", "8"],
+        ]);
+    }
+
+    #[test]
+    fn test_crawl_should_process() {
+        let server = TestServer::with_pages(&[
+            ("/", r#"<title>Home</title> <a href="/known">Known</a> <a href="/a">A</a> <a href="/b">B</a>"#),
+            ("/known", r#"<title>Known</title> <a href="/c">C</a>"#),
+            ("/a", "<title>Same</title>"),
+            ("/b", "<title>Same</title>"),
+        ]);
+        let known_url = server.url("/known");
+        let hashes = Arc::new(Mutex::new(vec![]));
+        let crawler_hashes = hashes.clone();
+        let crawler = Crawler::builder()
+            .should_process(move |url| *url != known_url)
+            .on_hashed(move |url, hash| crawler_hashes.lock().unwrap().push((url.path().to_owned(), hash)))
+            .build()
+            .unwrap();
+        assert_eq!(crawler.crawl(server.url("/")).unwrap().count(), 3);
+        assert_eq!(server.requested_paths(), ["/", "/a", "/b"]);
+
+        let hashes = hashes.lock().unwrap();
+        let paths: Vec<_> = hashes.iter().map(|(path, _)| &**path).collect();
+        assert_eq!(paths, ["/", "/a", "/b"]);
+        assert_ne!(hashes[0].1, hashes[1].1);
+        assert_eq!(hashes[1].1, hashes[2].1);
+    }
+
+    #[test]
+    fn test_check_page() {
+        let other_server = TestServer::start_on("127.0.0.2", |_| Response::new(500));
+        // Nothing listens on this port once the listener is dropped.
+        let closed_addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let other_url = other_server.url("/");
+        let server = TestServer::start(move |req| match (&*req.method, &*req.path) {
+            (_, "/") => Response::html(format!(r#"
+                <title>Home</title>
+                <a href="/live">Live</a> <a href="/dead#top">Dead</a> <a href="/dead">Dead again</a> <a href="/get-only">GET only</a>
+                <a href="{}">Other</a> <a href="http://{}/">Closed</a> <a href="mailto:someone@example.com">Mail</a>
+            "#, other_url, closed_addr)),
+            (_, "/live") => Response::html("<title>Live</title> <a href=\"/deeper\">Deeper</a>"),
+            ("HEAD", "/get-only") => Response::new(405),
+            ("GET", "/get-only") => Response::html("<title>GET only</title>"),
+            _ => Response::not_found(),
+        });
+
+        let check = Crawler::new().check_page(server.url("/")).unwrap();
+        assert_eq!(check.url, server.url("/"));
+        let statuses: Vec<_> = check.links.iter().map(|link| (link.url.clone(), link.status.map(|status| status.as_u16()))).collect();
+        assert_eq!(statuses, [
+            (server.url("/live"), Some(200)),
+            (server.url("/dead"), Some(404)),
+            (server.url("/get-only"), Some(200)),
+            (other_server.url("/"), Some(500)),
+            (format!("http://{}/", closed_addr).parse().unwrap(), None),
+        ]);
+        let requests: Vec<_> = server.requests().into_iter().map(|req| format!("{} {}", req.method, req.path)).collect();
+        assert_eq!(requests, ["GET /", "HEAD /live", "HEAD /dead", "HEAD /get-only", "GET /get-only"]);
+    }
+
+    #[test]
+    fn test_crawl_depth() {
+        let other_server = TestServer::with_pages(&[
+            ("/", r#"<title>Other</title> <a href="/x">X</a>"#),
+            ("/x", "<title>X</title>"),
+        ]);
+        let other_url = other_server.url("/");
+        let other_port = other_server.addr().port();
+        let server = TestServer::start(move |req| match &*req.path {
+            "/" => Response::html(format!(r#"<title>Home</title> <a href="/a">A</a> <a href="{}">Other</a>"#, other_url)),
+            "/a" => Response::html(r#"<title>A</title> <a href="/b">B</a> <a href="/">Home</a>"#),
+            "/b" => Response::html("<title>B</title>"),
+            _ => Response::not_found(),
+        });
+        let summarize = |crawler: Crawler| -> Vec<_> {
+            crawler.crawl(server.url("/")).unwrap().map(|result| (result.to_string(), result.depth)).collect()
+        };
+        let results = summarize(Crawler::builder().allowed_ports(vec![other_port]).build().unwrap());
+        let depths: Vec<_> = results.iter().map(|&(_, depth)| depth).collect();
+        assert_eq!(depths, [0, 1, 1, 2, 2]);
+
+        // Limiting depth (beyond what the site has) or ordering results changes nothing.
+        assert_eq!(summarize(Crawler::builder().allowed_ports(vec![other_port]).max_depth(10).build().unwrap()), results);
+        assert_eq!(summarize(Crawler::builder().allowed_ports(vec![other_port]).ordered(true).build().unwrap()), results);
+    }
+
+    #[test]
+    fn test_crawl_fetcher() {
+        struct FileFetcher;
+
+        impl Fetcher for FileFetcher {
+            fn fetch(&self, url: &Url) -> Result<FetchedContent, FetchWebPageError> {
+                let path = url.to_file_path().map_err(|_| FetchWebPageError::Io(io::Error::other("not a file path")))?;
+                let content_type = if path.extension() == Some("html".as_ref()) { "text/html" } else { "text/plain" };
+                Ok(FetchedContent {
+                    content_type: content_type.to_owned(),
+                    body: std::fs::read(path).map_err(FetchWebPageError::Io)?,
+                })
+            }
+        }
+
+        let dir = std::env::temp_dir().join(format!("web-crawler-test-fetcher-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("docs")).unwrap();
+        std::fs::write(dir.join("index.html"), r#"
+            <title>Home</title> <a href="docs/a.html">A</a> <a href="missing.html">Missing</a>
+            <a href="notes.txt">Notes</a> <a href="mailto:someone@example.com">Mail</a>
+        "#).unwrap();
+        std::fs::write(dir.join("docs/a.html"), r#"<title>A</title> <a href="../index.html">Home</a>"#).unwrap();
+        std::fs::write(dir.join("notes.txt"), "Notes").unwrap();
+
+        let seed = Url::from_file_path(dir.join("index.html")).unwrap();
+        let crawler = Crawler::builder().fetcher("file", FileFetcher).build().unwrap();
+        let results: Vec<_> = crawler.crawl_attempts(seed.clone()).unwrap().collect();
+        // Checking the seed's links goes through the fetcher too.
+        let check = crawler.check_page(seed.clone()).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+        let statuses: Vec<_> = check.links.iter().map(|link| (link.url.clone(), link.status.map(|status| status.as_u16()))).collect();
+        assert_eq!(statuses, [
+            (seed.join("docs/a.html").unwrap(), Some(200)),
+            (seed.join("missing.html").unwrap(), None),
+            (seed.join("notes.txt").unwrap(), Some(200)),
+        ]);
+
+        let titles: Vec<_> = results.iter().filter_map(|result| result.as_ref().ok()?.page.as_ref()).map(|page| &*page.title).collect();
+        assert_eq!(titles, ["Home", "A"]);
+        let failures: Vec<_> = results.iter().filter_map(|result| Some(Url::clone(&result.as_ref().err()?.url))).collect();
+        assert_eq!(failures, [seed.join("missing.html").unwrap(), seed.join("notes.txt").unwrap()]);
+
+        // Without the fetcher, the seed isn't even valid.
+        assert!(is_match!(Crawler::new().crawl(seed), Err(CrawlError::InvalidSeed(_))));
+    }
+
+    #[test]
+    fn test_append_jsonl() {
+        let server = TestServer::with_pages(&[
+            ("/", r#"<title>Home "page"</title> <a href="/a">A</a> <a href="/b">B</a>"#),
+            ("/a", "<title>A</title>"),
+            ("/b", "<title>B</title>"),
+        ]);
+        let results: Vec<_> = Crawler::new().crawl(server.url("/")).unwrap().collect();
+        assert_eq!(results.len(), 3);
+
+        // Write the first two results before being "interrupted" partway through the third.
+        let mut jsonl = vec![];
+        write_jsonl(&results[..2], &mut jsonl).unwrap();
+        let first_line = String::from_utf8(jsonl.clone()).unwrap().lines().next().unwrap().to_owned();
+        assert_eq!(first_line, format!(
+            r#"{{"url":"{}","status":200,"depth":0,"content_type":"text/html","byte_size":{},"title":"Home \"page\"","links":["{}","{}"]}}"#,
+            server.url("/"), results[0].byte_size, server.url("/a"), server.url("/b"),
+        ));
+        jsonl.extend_from_slice(format!(r#"{{"url":"{}","sta"#, server.url("/b")).as_bytes());
+        jsonl.push(b'\n');
+
+        let mut written_urls = read_jsonl_urls(&*jsonl).unwrap();
+        assert_eq!(written_urls, [server.url("/"), server.url("/a")].iter().cloned().collect());
+        append_jsonl(&results, &mut written_urls, &mut jsonl).unwrap();
+        append_jsonl(&results, &mut written_urls, &mut jsonl).unwrap();
+        let urls: Vec<_> = String::from_utf8(jsonl).unwrap().lines()
+            .filter(|line| line.ends_with('}'))
+            .map(|line| line.split('"').nth(3).unwrap().to_owned())
+            .collect();
+        assert_eq!(urls, [server.url("/").as_str(), server.url("/a").as_str(), server.url("/b").as_str()]);
+    }
+
+    #[test]
+    fn test_crawl_preserves_path_case() {
+        let server = TestServer::start(|req| match &*req.path {
+            "/Docs/ReadMe.HTML?Lang=EN" => Response::html(r#"<title>Read me</title> <a href="HTTP://EXAMPLE.com/Docs/%7eUser/%c3%A9?Q=Yes#Top">User</a>"#),
+            _ => Response::html("<title>Other</title>"),
+        });
+        let crawler = Crawler::builder()
+            .resolve("example.com", server.addr())
+            .sort_query_params(true)
+            .trailing_slash(TrailingSlash::AddToDirectories)
+            .build()
+            .unwrap();
+        let urls: Vec<_> = crawler.crawl("HTTP://Example.COM/Docs/ReadMe.HTML?Lang=EN").unwrap()
+            .map(|result| result.url.to_string())
+            .collect();
+        assert_eq!(urls, ["http://example.com/Docs/ReadMe.HTML?Lang=EN", "http://example.com/Docs/~User/%C3%A9/?Q=Yes"]);
+        assert_eq!(server.requested_paths(), ["/Docs/ReadMe.HTML?Lang=EN", "/Docs/~User/%C3%A9/?Q=Yes"]);
+    }
+
+    #[test]
+    fn test_crawl_host_header() {
+        let server = TestServer::start(|req| match (req.header("Host"), &*req.path) {
+            (Some("shop.example"), "/") => Response::html(r#"<title>Shop</title> <a href="/cart">Cart</a>"#),
+            (Some("shop.example"), "/cart") => Response::html("<title>Cart</title>"),
+            (Some("blog.example"), "/") => Response::html("<title>Blog</title>"),
+            _ => Response::html("<title>Default</title>"),
+        });
+        let titles = |crawler: Crawler, seed: &str| -> Vec<_> {
+            crawler.crawl(seed).unwrap().map(|result| result.page.unwrap().title).collect()
+        };
+        let seed = server.url("/");
+        assert_eq!(titles(Crawler::new(), seed.as_str()), ["Default"]);
+        assert_eq!(titles(Crawler::builder().host_header("127.0.0.1", "shop.example").build().unwrap(), seed.as_str()), ["Shop", "Cart"]);
+        assert_eq!(server.requests().iter().filter(|req| req.header("Host") == Some("shop.example")).count(), 2);
+
+        // It also takes precedence over the `Host` header sent for resolved hosts.
+        let crawler = Crawler::builder()
+            .resolve("example.com", server.addr())
+            .host_header("example.com", "blog.example")
+            .build()
+            .unwrap();
+        assert_eq!(titles(crawler, "http://example.com/"), ["Blog"]);
+    }
+
+    #[test]
+    fn test_crawl_pagination_loop() {
+        let server = TestServer::start(|req| {
+            let url: Url = format!("http://localhost{}", req.path).parse().unwrap();
+            let page: usize = match url.query_pairs().find(|(name, _)| name == "page") {
+                Some((_, page)) => page.parse().unwrap(),
+                None => return Response::html("<title>Item</title>"),
+            };
+            // Past page 3, every page shows the same items as it does.
+            let items = page.min(3);
+            Response::html(format!(r#"
+                <title>Page {page}</title> <a href="/items/{items}a">A</a> <a href="/items/{items}b">B</a>
+                <a href="/list?page=1&amp;sort=new">First</a> <a href="/list?sort=new&amp;page={next}">Next</a>
+            "#, page = page, items = items, next = page + 1))
+        });
+        let crawler = Crawler::builder().pagination_loop("page", 2).max_pages(100).build().unwrap();
+        let titles: Vec<_> = crawler.crawl(server.url("/list?page=1&sort=new")).unwrap()
+            .map(|result| result.page.unwrap().title)
+            .filter(|title| title.starts_with("Page"))
+            .collect();
+        assert_eq!(titles, ["Page 1", "Page 2", "Page 3", "Page 4"]);
+
+        // Otherwise, only the page limit stops the crawl.
+        let crawler = Crawler::builder().max_pages(20).build().unwrap();
+        assert_eq!(crawler.crawl(server.url("/list?page=1&sort=new")).unwrap().count(), 20);
+    }
+
+    #[test]
+    fn test_fetch_unsupported_charset() {
+        let server = TestServer::start(|req| match &*req.path {
+            "/" => Response::html(r#"<title>Home</title> <a href="/klingon">Klingon</a>"#),
+            "/klingon" => Response::new(200).header("Content-Type", "text/html; charset=x-klingon").body("<title>Qapla'</title>"),
+            "/bom" => Response::new(200).header("Content-Type", "text/html; charset=x-klingon").body("\u{feff}<title>BOM</title>"),
+            _ => Response::not_found(),
+        });
+
+        let client = Client::new();
+        let err = fetch_web_page(client.get(server.url("/klingon")), &FetchOptions::default()).err().unwrap();
+        assert!(is_match!(err, FetchWebPageError::UnsupportedCharset(ref charset) if charset == "x-klingon"));
+        // A byte order mark says what the encoding is regardless.
+        let page = fetch_web_page(client.get(server.url("/bom")), &FetchOptions::default()).unwrap().html.unwrap();
+        assert_eq!(page.body, "<title>BOM</title>");
+
+        let crawler = Crawler::builder().build().unwrap();
+        let failures: Vec<_> = crawler.crawl_attempts(server.url("/")).unwrap().filter_map(Result::err).collect();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].url.path(), "/klingon");
+        assert!(is_match!(failures[0].error, FetchWebPageError::UnsupportedCharset(_)));
+    }
+
+    #[test]
+    fn test_crawl_max_frontier() {
+        let server = TestServer::start(|req| match &*req.path {
+            "/" => {
+                let links: String = (0..20).map(|i| format!(r#"<a href="/{}">{}</a>"#, i, i)).collect();
+                Response::html(format!("<title>Home</title> {} <a href=\"/\">Home</a>", links))
+            }
+            _ => Response::html(r#"<title>Page</title> <a href="/19">19</a>"#),
+        });
+
+        let crawler = Crawler::builder().max_frontier(5).build().unwrap();
+        let mut results = crawler.crawl(server.url("/")).unwrap();
+        let frontier_len = results.frontier_len_handle();
+        let mut paths = vec![];
+        for result in results.by_ref() {
+            assert!(frontier_len.get() <= 5);
+            paths.push(result.url.path().to_owned());
+        }
+        // The last link is dropped at first, but followed once it's found again with room to spare.
+        assert_eq!(paths, ["/", "/0", "/1", "/2", "/3", "/4", "/19"]);
+        assert_eq!(results.dropped_urls(), 15);
+    }
+
+
+    #[test]
+    fn test_crawl_response_cache_vary() {
+        // Every variant has the same `ETag`, so only keying on `Accept-Language` tells them apart.
+        let server = TestServer::start(|req| {
+            if req.header("If-None-Match") == Some("\"v1\"") {
+                return Response::new(304).header("ETag", "\"v1\"").header("Vary", "Accept-Language");
+            }
+            let title = if req.header("Accept-Language") == Some("fr") { "Bonjour" } else { "Hello" };
+            Response::html(format!("<title>{}</title>", title)).header("ETag", "\"v1\"").header("Vary", "Accept-Language")
+        });
+
+        let cache = ResponseCache::new();
+        let crawl = |language| {
+            let crawler = Crawler::builder()
+                .request_header(header::ACCEPT_LANGUAGE, HeaderValue::from_static(language))
+                .response_cache(cache.clone())
+                .build()
+                .unwrap();
+            crawler.crawl(server.url("/")).unwrap().map(|result| result.page.unwrap().title).collect::<Vec<_>>()
+        };
+        assert_eq!(crawl("en"), ["Hello"]);
+        assert_eq!(crawl("fr"), ["Bonjour"]);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(crawl("en"), ["Hello"]);
+        assert_eq!(crawl("fr"), ["Bonjour"]);
+
+        let conditional: Vec<_> = server.requests().iter()
+            .map(|req| (req.header("Accept-Language").unwrap().to_owned(), req.header("If-None-Match").is_some()))
+            .collect();
+        assert_eq!(conditional, [
+            ("en".to_owned(), false),
+            ("fr".to_owned(), false),
+            ("en".to_owned(), true),
+            ("fr".to_owned(), true),
+        ]);
+    }
+
+    #[test]
+    fn test_crawl_response_cache_not_modified() {
+        let server = TestServer::start(|req| match req.header("If-None-Match") {
+            // Each revalidation hands out a new `ETag`, as a server that versions its validators might.
+            Some("\"v1\"") => Response::new(304).header("ETag", "\"v2\"").header("X-Served-By", "revalidation"),
+            Some(_) => Response::new(304),
+            None => Response::html("<title>Home</title>").header("ETag", "\"v1\"").header("X-Served-By", "origin"),
+        });
+        let cache = ResponseCache::new();
+        let crawler = Crawler::builder().response_cache(cache.clone()).build().unwrap();
+        let crawl = || crawler.crawl(server.url("/")).unwrap().next().unwrap();
+
+        let origin = crawl();
+        let revalidated = crawl();
+        assert_eq!(revalidated.status, StatusCode::OK);
+        assert_eq!(revalidated.http_version, Version::HTTP_11);
+        assert_eq!(revalidated.page, origin.page);
+        assert_eq!(revalidated.headers["ETag"], "\"v2\"");
+        assert_eq!(revalidated.headers["X-Served-By"], "revalidation");
+        assert_eq!(revalidated.headers["Content-Type"], origin.headers["Content-Type"]);
+        assert_eq!(crawl().headers["ETag"], "\"v2\"");
+
+        // The cache kept the new `ETag` to revalidate with.
+        let validators: Vec<_> = server.requests().iter().map(|req| req.header("If-None-Match").map(str::to_owned)).collect();
+        assert_eq!(validators, [None, Some("\"v1\"".to_owned()), Some("\"v2\"".to_owned())]);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_crawl_max_frontier_max_hosts() {
+        let dropped_server = TestServer::start_on("127.0.0.2", |_| Response::html("<title>Dropped</title>"));
+        let later_server = TestServer::start_on("127.0.0.3", |_| Response::html("<title>Later</title>"));
+        let dropped_url = dropped_server.url("/");
+        let later_url = later_server.url("/");
+        let seed_server = TestServer::start(move |req| match &*req.path {
+            "/" => Response::html(format!(
+                r#"<title>Seed</title> <a href="/1">1</a> <a href="/2">2</a> <a href="/3">3</a> <a href="{}">Dropped</a>"#,
+                dropped_url,
+            )),
+            "/1" => Response::html(format!(r#"<title>1</title> <a href="{}">Later</a>"#, later_url)),
+            _ => Response::html("<title>Page</title>"),
+        });
+
+        // The link that was dropped doesn't stop the one found later from being the second host.
+        let crawler = Crawler::builder()
+            .max_hosts(2)
+            .max_frontier(3)
+            .allowed_ports(vec![dropped_server.addr().port(), later_server.addr().port()])
+            .build().unwrap();
+        let mut results = crawler.crawl(seed_server.url("/")).unwrap();
+        let titles: Vec<_> = results.by_ref().map(|result| result.page.unwrap().title).collect();
+        assert_eq!(titles, ["Seed", "1", "Page", "Page", "Later"]);
+        assert_eq!(results.dropped_urls(), 1);
+        assert_eq!(dropped_server.requested_paths().len(), 0);
     }
 }
@@ -0,0 +1,50 @@
+use reqwest::Url;
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use url::Position;
+
+// The file name used for URLs whose path ends in a slash.
+const INDEX_FILE_NAME: &str = "index.html";
+
+// Saves the given body under `dir`, at a path mirroring the URL's host and path.
+// NOTE: a site that serves both `/a` and `/a/b` can't be mirrored completely, since `a` can't be both a file and a
+// directory; whichever is saved second fails.
+pub fn save(dir: &Path, url: &Url, body: &[u8]) -> io::Result<()> {
+    let path = mirror_path(dir, url);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, body)
+}
+
+fn mirror_path(dir: &Path, url: &Url) -> PathBuf {
+    let mut path = dir.join(sanitize(&url[Position::BeforeHost..Position::AfterPort]));
+    let segments: Vec<_> = url.path_segments().into_iter().flatten().collect();
+    let (file_name, dirs) = segments.split_last().unwrap_or((&"", &[]));
+    for segment in dirs.iter().filter(|segment| !segment.is_empty()) {
+        path.push(sanitize(segment));
+    }
+
+    let mut file_name = if file_name.is_empty() { INDEX_FILE_NAME.to_owned() } else { sanitize(file_name) };
+    // Keep pages that differ only by their query apart.
+    if let Some(query) = url.query() {
+        file_name.push('_');
+        file_name.push_str(&sanitize(query));
+    }
+    path.push(file_name);
+    path
+}
+
+// Replaces anything that may not be safe in a file name (on any common platform), and never returns `.` or `..`.
+fn sanitize(name: &str) -> String {
+    let name: String = name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || "-._~%=&+,".contains(c) { c } else { '_' })
+        .collect();
+    match &*name {
+        "" | "." | ".." => "_".to_owned(),
+        _ => name,
+    }
+}
@@ -0,0 +1,87 @@
+use html5ever::parse_document;
+use html5ever::rcdom::{Handle, NodeData, RcDom};
+use html5ever::tendril::stream::TendrilSink;
+use html5ever::tendril::StrTendril;
+
+use select::document::Document;
+use select::node::{Data, Raw};
+
+use std::time::Instant;
+
+// How much HTML is parsed between checks of the deadline.
+const CHUNK_SIZE: usize = 16 * 1024;
+
+// Parses a page into a document as `Document::from` does, unless the parse is still going at the given deadline.
+// NOTE: the parser is fed the page a chunk at a time so that it can be given up on partway, which means a thread left
+// parsing a pathological page stops soon after nothing is waiting for it any more.
+pub(crate) fn parse_document_by(html: &str, deadline: Instant) -> Option<Document> {
+    let mut parser = parse_document(RcDom::default(), Default::default());
+    let mut rest = html;
+    while !rest.is_empty() {
+        if Instant::now() >= deadline {
+            return None;
+        }
+        let mut end = rest.len().min(CHUNK_SIZE);
+        while !rest.is_char_boundary(end) {
+            end += 1;
+        }
+        let (chunk, tail) = rest.split_at(end);
+        parser.process(StrTendril::from(chunk));
+        rest = tail;
+    }
+    let dom = parser.finish();
+    if Instant::now() >= deadline {
+        return None;
+    }
+
+    let mut document = Document { nodes: vec![] };
+    append_children(&mut document, &dom.document, None);
+    Some(document)
+}
+
+// Adds the children of the given DOM node to the document, under the given parent, as `Document::from` does.
+// NOTE: select has no way to build a document from a DOM parsed elsewhere, so this fills in its (public) nodes by hand,
+// mirroring `Document::from` in select 0.4.3, which is why Cargo.toml pins that exact version. Check this against any
+// newer version before upgrading.
+fn append_children(document: &mut Document, node: &Handle, parent: Option<usize>) {
+    let mut prev = None;
+    for child in node.children.borrow().iter() {
+        let data = match &child.data {
+            NodeData::Text { contents } => Data::Text(contents.borrow().clone()),
+            NodeData::Comment { contents } => Data::Comment(contents.clone()),
+            NodeData::Element { name, attrs, .. } => {
+                let attrs = attrs.borrow().iter().map(|attr| (attr.name.clone(), attr.value.clone())).collect();
+                Data::Element(name.clone(), attrs)
+            }
+            // Other nodes (e.g., the doctype) are left out, and, as with `Document::from`, break the chain of siblings.
+            _ => {
+                prev = None;
+                continue;
+            }
+        };
+
+        let index = document.nodes.len();
+        document.nodes.push(Raw {
+            index,
+            parent,
+            prev,
+            next: None,
+            first_child: None,
+            last_child: None,
+            data,
+        });
+        if let Some(parent) = parent {
+            let parent = &mut document.nodes[parent];
+            parent.first_child = parent.first_child.or(Some(index));
+            parent.last_child = Some(index);
+        }
+        if let Some(prev) = prev {
+            document.nodes[prev].next = Some(index);
+        }
+
+        if let NodeData::Element { .. } = child.data {
+            append_children(document, child, Some(index));
+        }
+        prev = Some(index);
+    }
+}
@@ -0,0 +1,66 @@
+use reqwest::Url;
+
+use url::Position;
+
+// The rules of a `robots.txt` that apply to us, along with the sitemaps it advertises.
+// NOTE: only the group for all user agents (`User-agent: *`) is used, since the crawler has no user agent of its own,
+// and wildcards (`*` and `$`) in paths aren't supported.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Robots {
+    // Each rule as whether it allows (rather than disallows) paths, and the path prefix it matches.
+    rules: Vec<(bool, String)>,
+    pub sitemaps: Vec<Url>,
+}
+
+impl Robots {
+    // Robots that allow everything, for hosts without a usable `robots.txt`.
+    pub fn allow_all() -> Robots {
+        Robots::default()
+    }
+
+    pub fn parse(text: &str, url: &Url) -> Robots {
+        let mut robots = Robots::default();
+        // Whether the current group applies to us, and whether we've seen any rules in it yet (after which a
+        // `User-agent` line starts a new group).
+        let mut in_group = false;
+        let mut group_has_rules = false;
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let mut field_value = line.splitn(2, ':');
+            let field = field_value.next().unwrap_or_default().trim().to_ascii_lowercase();
+            let value = match field_value.next() {
+                Some(value) => value.trim(),
+                None => continue,
+            };
+            match &*field {
+                "user-agent" => {
+                    if group_has_rules {
+                        in_group = false;
+                        group_has_rules = false;
+                    }
+                    in_group |= value == "*";
+                }
+                "allow" | "disallow" => {
+                    group_has_rules = true;
+                    // An empty `Disallow` allows everything, so it needn't be a rule at all.
+                    if in_group && !value.is_empty() {
+                        robots.rules.push((field == "allow", value.to_owned()));
+                    }
+                }
+                // Sitemaps don't belong to any group.
+                "sitemap" => robots.sitemaps.extend(url.join(value).ok()),
+                _ => {}
+            }
+        }
+        robots
+    }
+
+    pub fn is_allowed(&self, url: &Url) -> bool {
+        let path = &url[Position::BeforePath..Position::AfterQuery];
+        // The most specific (i.e., longest) matching rule wins, with `Allow` winning ties.
+        self.rules.iter()
+            .filter(|(_, prefix)| path.starts_with(&**prefix))
+            .max_by_key(|&&(allow, ref prefix)| (prefix.len(), allow))
+            .is_none_or(|&(allow, _)| allow)
+    }
+}
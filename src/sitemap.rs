@@ -0,0 +1,43 @@
+use std::io::{self, Write};
+
+use crate::CrawlResult;
+
+// Writes a `sitemap.xml` listing the URLs of the given results that are on the same host as the first (i.e., the
+// seed), with the `lastmod` of each where it's known.
+// NOTE: a sitemap may list at most 50,000 URLs, so bigger sites would need to be split across several sitemaps.
+pub fn write_sitemap<'a, W: Write>(results: impl IntoIterator<Item = &'a CrawlResult>, mut writer: W) -> io::Result<()> {
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(writer, r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">"#)?;
+
+    let mut results = results.into_iter().peekable();
+    let host = results.peek().map(|result| result.url.host_str().map(str::to_owned));
+    for result in results {
+        if Some(result.url.host_str().map(str::to_owned)) != host {
+            continue;
+        }
+
+        writeln!(writer, "  <url>")?;
+        writeln!(writer, "    <loc>{}</loc>", escape_xml(result.url.as_str()))?;
+        if let Some(last_modified) = result.last_modified {
+            writeln!(writer, "    <lastmod>{}</lastmod>", time::at_utc(last_modified).rfc3339())?;
+        }
+        writeln!(writer, "  </url>")?;
+    }
+
+    writeln!(writer, "</urlset>")
+}
+
+fn escape_xml(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,125 @@
+use html5ever::tendril::StrTendril;
+use html5ever::tokenizer::states::RawKind;
+use html5ever::tokenizer::{BufferQueue, Tag, TagKind, Token, TokenSink, TokenSinkResult, Tokenizer, TokenizerOpts};
+
+use reqwest::Url;
+
+use std::collections::HashSet;
+
+use crate::{GetWebPageInfoError, Link, WebPageInfo};
+
+// Extracts the title and links of a page (along with the types that links declare) by tokenizing its HTML, without
+// building a document. Everything else about the page is left empty.
+// NOTE: without a tree builder, nothing tells the tokenizer which elements hold text rather than markup, so it's told
+// about the standard ones (e.g., `<script>`) here; anything that relies on the document being well-formed (e.g., an
+// `<a>` inside an `<svg>` being ignored) may differ from the full parse.
+pub(crate) fn get_web_page_info(
+    url: &Url,
+    html: &str,
+    link_attributes: &[String],
+) -> Result<WebPageInfo, GetWebPageInfoError> {
+    let sink = LinkSink {
+        link_attributes,
+        title: None,
+        in_title: false,
+        base: None,
+        links: vec![],
+    };
+    let mut tokenizer = Tokenizer::new(sink, TokenizerOpts::default());
+    let mut input = BufferQueue::new();
+    input.push_back(StrTendril::from(html));
+    let _ = tokenizer.feed(&mut input);
+    tokenizer.end();
+    let sink = tokenizer.sink;
+
+    let title = sink.title.ok_or(GetWebPageInfoError::NoTitle)?.trim().to_owned();
+    // Resolve relative links against the `<base>` element if there is one, as for the full parse.
+    let base = sink.base.and_then(|href| url.join(&href).ok()).unwrap_or_else(|| url.clone());
+    let link_urls: Vec<_> = sink.links.into_iter().filter_map(|(href, link_type)| {
+        // Ignore links with invalid URLs.
+        let link_url = base.join(&href).ok()?;
+        Some((link_url, link_type))
+    }).collect();
+
+    let mut links_seen = HashSet::new();
+    let links: Vec<_> = link_urls.iter()
+        .map(|(link_url, _)| link_url.clone())
+        .filter(|link_url| links_seen.insert(link_url.clone()))
+        .collect();
+    let link_types = link_urls.into_iter().filter_map(|(link_url, link_type)| {
+        // Ignore any parameters, as for the `Content-Type` header.
+        let mime = link_type?.split(';').next()?.trim().to_ascii_lowercase();
+        Some((link_url, mime))
+    }).collect();
+
+    let link_details = Link::all_relative_to(&links, url, false);
+    Ok(WebPageInfo {
+        title,
+        links,
+        headings: vec![],
+        resource_hints: vec![],
+        images: vec![],
+        link_elements: vec![],
+        link_types,
+        link_details,
+    })
+}
+
+struct LinkSink<'a> {
+    link_attributes: &'a [String],
+    // The text of the first `<title>`, once one has started.
+    title: Option<String>,
+    in_title: bool,
+    // The `href` of the first `<base>` that has one.
+    base: Option<String>,
+    // Each link as its (unresolved) URL and its `type` attribute, if any.
+    links: Vec<(String, Option<String>)>,
+}
+
+impl LinkSink<'_> {
+    fn process_start_tag(&mut self, tag: &Tag) -> TokenSinkResult<()> {
+        let attr = |name: &str| tag.attrs.iter().find(|attr| &*attr.name.local == name).map(|attr| attr.value.to_string());
+        match &*tag.name {
+            "a" | "area" => {
+                for attribute in self.link_attributes {
+                    if let Some(href) = attr(attribute) {
+                        self.links.push((href, attr("type")));
+                    }
+                }
+            }
+            "base" if self.base.is_none() => self.base = attr("href"),
+            "title" => {
+                if self.title.is_none() && !tag.self_closing {
+                    self.title = Some(String::new());
+                    self.in_title = true;
+                }
+                return TokenSinkResult::RawData(RawKind::Rcdata);
+            }
+            "textarea" => return TokenSinkResult::RawData(RawKind::Rcdata),
+            "script" => return TokenSinkResult::RawData(RawKind::ScriptData),
+            "style" | "xmp" | "iframe" | "noembed" | "noframes" => return TokenSinkResult::RawData(RawKind::Rawtext),
+            "plaintext" => return TokenSinkResult::Plaintext,
+            _ => {}
+        }
+        TokenSinkResult::Continue
+    }
+}
+
+impl TokenSink for LinkSink<'_> {
+    type Handle = ();
+
+    fn process_token(&mut self, token: Token, _line_number: u64) -> TokenSinkResult<()> {
+        match token {
+            Token::TagToken(tag) => match tag.kind {
+                TagKind::StartTag => return self.process_start_tag(&tag),
+                TagKind::EndTag if &*tag.name == "title" => self.in_title = false,
+                TagKind::EndTag => {}
+            },
+            Token::CharacterTokens(text) if self.in_title => {
+                self.title.get_or_insert_with(String::new).push_str(&text);
+            }
+            _ => {}
+        }
+        TokenSinkResult::Continue
+    }
+}
@@ -0,0 +1,238 @@
+// A minimal HTTP/1.1 server for tests, so they need not rely on the Web.
+
+use reqwest::Url;
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| &**v)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn new(status: u16) -> Response {
+        Response {
+            status,
+            headers: vec![],
+            body: vec![],
+        }
+    }
+
+    pub fn html(body: impl Into<String>) -> Response {
+        Response::new(200)
+            .header("Content-Type", "text/html; charset=utf-8")
+            .body(body.into())
+    }
+
+    pub fn not_found() -> Response {
+        Response::new(404)
+            .header("Content-Type", "text/html; charset=utf-8")
+            .body("<title>Not Found</title>")
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Response {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Response {
+        self.body = body.into();
+        self
+    }
+}
+
+type Handler = dyn Fn(&Request) -> Response + Send + Sync;
+
+pub struct TestServer {
+    addr: SocketAddr,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl TestServer {
+    pub fn start(handler: impl Fn(&Request) -> Response + Send + Sync + 'static) -> TestServer {
+        TestServer::start_on("127.0.0.1", handler)
+    }
+
+    // Any address in `127.0.0.0/8` may be used, which lets tests simulate distinct hosts.
+    pub fn start_on(ip: &str, handler: impl Fn(&Request) -> Response + Send + Sync + 'static) -> TestServer {
+        let listener = TcpListener::bind((ip, 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let requests = Arc::new(Mutex::new(vec![]));
+        let handler: Arc<Handler> = Arc::new(handler);
+
+        let server_requests = requests.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => continue,
+                };
+                let requests = server_requests.clone();
+                let handler = handler.clone();
+                thread::spawn(move || handle_connection(stream, &*handler, &requests));
+            }
+        });
+
+        TestServer {
+            addr,
+            requests,
+        }
+    }
+
+    // Serves the given `(path, html)` pages, and 404 for anything else.
+    pub fn with_pages(pages: &[(&str, &str)]) -> TestServer {
+        let pages: Vec<(String, String)> = pages.iter()
+            .map(|&(path, html)| (path.to_owned(), html.to_owned()))
+            .collect();
+        TestServer::start(move |req| {
+            pages.iter()
+                .find(|(path, _)| *path == req.path)
+                .map_or_else(Response::not_found, |(_, html)| Response::html(&**html))
+        })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn url(&self, path: &str) -> Url {
+        format!("http://{}{}", self.addr, path).parse().unwrap()
+    }
+
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    pub fn requested_paths(&self) -> Vec<String> {
+        self.requests().into_iter().map(|req| req.path).collect()
+    }
+}
+
+// A listener that never accepts connections, with its backlog filled up, so that connecting to it times out (much like
+// connecting to an unroutable address).
+pub struct BlackHole {
+    listener: TcpListener,
+    _streams: Vec<TcpStream>,
+}
+
+impl BlackHole {
+    pub fn start() -> BlackHole {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut streams = vec![];
+        while let Ok(stream) = TcpStream::connect_timeout(&addr, Duration::from_millis(100)) {
+            streams.push(stream);
+        }
+        BlackHole {
+            listener,
+            _streams: streams,
+        }
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.listener.local_addr().unwrap()
+    }
+}
+
+fn handle_connection(stream: TcpStream, handler: &Handler, requests: &Mutex<Vec<Request>>) {
+    let mut reader = BufReader::new(match stream.try_clone() {
+        Ok(stream) => stream,
+        Err(_) => return,
+    });
+    let mut stream = stream;
+
+    // Serve requests on this connection until the client closes it.
+    while let Some(req) = read_request(&mut reader) {
+        requests.lock().unwrap().push(req.clone());
+        let resp = handler(&req);
+        if write_response(&mut stream, &req, &resp).is_err() {
+            return;
+        }
+    }
+}
+
+fn read_request(reader: &mut impl BufRead) -> Option<Request> {
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).ok()? == 0 {
+        return None;
+    }
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next()?.to_owned();
+    let path = parts.next()?.to_owned();
+
+    let mut headers = vec![];
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).ok()?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let mut header = line.splitn(2, ':');
+        let name = header.next()?.trim().to_owned();
+        let value = header.next().unwrap_or("").trim().to_owned();
+        headers.push((name, value));
+    }
+
+    let mut req = Request {
+        method,
+        path,
+        headers,
+        body: vec![],
+    };
+    let content_length = req.header("Content-Length").and_then(|s| s.parse().ok()).unwrap_or(0);
+    req.body = vec![0; content_length];
+    reader.read_exact(&mut req.body).ok()?;
+    Some(req)
+}
+
+fn write_response(stream: &mut impl Write, req: &Request, resp: &Response) -> std::io::Result<()> {
+    let chunked = resp.headers.iter()
+        .any(|(n, v)| n.eq_ignore_ascii_case("Transfer-Encoding") && v.eq_ignore_ascii_case("chunked"));
+
+    write!(stream, "HTTP/1.1 {} Test\r\n", resp.status)?;
+    for (name, value) in &resp.headers {
+        write!(stream, "{}: {}\r\n", name, value)?;
+    }
+    if !chunked {
+        write!(stream, "Content-Length: {}\r\n", resp.body.len())?;
+    }
+    write!(stream, "\r\n")?;
+
+    if req.method != "HEAD" {
+        if chunked {
+            // Split the body into several chunks, to exercise streaming on the client side.
+            for chunk in resp.body.chunks(16) {
+                write!(stream, "{:x}\r\n", chunk.len())?;
+                stream.write_all(chunk)?;
+                write!(stream, "\r\n")?;
+            }
+            write!(stream, "0\r\n\r\n")?;
+        } else {
+            stream.write_all(&resp.body)?;
+        }
+    }
+    stream.flush()
+}
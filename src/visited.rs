@@ -0,0 +1,47 @@
+use reqwest::Url;
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+// The set of URLs the crawler has already seen (i.e., visited or queued), so that it never visits a page twice.
+// NOTE: URLs are inserted by reference, and shared with the frontier and the results, so that a set that keeps them
+// needn't allocate them again.
+pub trait VisitedSet {
+    fn insert(&mut self, url: &Arc<Url>);
+
+    fn contains(&self, url: &Url) -> bool;
+}
+
+// The default visited set, which is kept in memory.
+#[derive(Clone, Debug, Default)]
+pub struct HashVisitedSet {
+    urls: HashSet<Arc<Url>>,
+}
+
+impl HashVisitedSet {
+    pub fn new() -> HashVisitedSet {
+        HashVisitedSet::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> HashVisitedSet {
+        HashVisitedSet {
+            urls: HashSet::with_capacity(capacity),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.urls.capacity()
+    }
+}
+
+impl VisitedSet for HashVisitedSet {
+    fn insert(&mut self, url: &Arc<Url>) {
+        if !self.urls.contains(url) {
+            self.urls.insert(Arc::clone(url));
+        }
+    }
+
+    fn contains(&self, url: &Url) -> bool {
+        self.urls.contains(url)
+    }
+}